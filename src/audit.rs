@@ -0,0 +1,133 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Audit trail of the changes made to control groups
+
+use crate::internal;
+use crate::CGroup;
+use serde::Serialize;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// Where to append records, and the invocation that they are attributed to.
+struct AuditLog {
+	path: PathBuf,
+	command: String,
+}
+
+static AUDIT_LOG: OnceLock<AuditLog> = OnceLock::new();
+
+/// One line of the audit log.
+#[derive(Serialize, Debug)]
+struct Record<'a> {
+	/// When the change was made, in RFC 3339 format.
+	timestamp: String,
+	/// Command line of the invocation that made the change.
+	command: &'a str,
+	/// Normalized name of the control group.
+	cgroup: String,
+	/// What was done, such as "create" or "set".
+	action: &'a str,
+	/// Interface file that was written, such as "memory.max".
+	#[serde(skip_serializing_if = "Option::is_none")]
+	key: Option<&'a str>,
+	/// Value that was written.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	value: Option<&'a str>,
+	/// Value that the kernel reports after a successful write, which may be canonicalized.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	effective: Option<String>,
+	/// "ok", or the error that the change failed with.
+	result: String,
+}
+
+pub(crate) fn enable(path: PathBuf, command: String) {
+	let _ = AUDIT_LOG.set(AuditLog { path, command });
+}
+
+/// Appends a record of a change to the audit log, if it is enabled.
+///
+/// After a successful "set", the file is read back so that the record shows the effective value.
+pub(crate) fn record(cgroup: &CGroup, action: &str, key: Option<&str>, value: Option<&str>, result: &io::Result<()>) {
+	let Some(log) = AUDIT_LOG.get() else {
+		return;
+	};
+	let effective = match (action, key, result) {
+		("set", Some(key), Ok(())) => cgroup.get_restriction(key).ok(),
+		_ => None,
+	};
+	let record = Record {
+		timestamp: internal::format_timestamp(SystemTime::now()),
+		command: &log.command,
+		cgroup: cgroup.canonical_name(),
+		action,
+		key,
+		value,
+		effective,
+		result: match result {
+			Ok(()) => "ok".to_string(),
+			Err(e) => e.to_string(),
+		},
+	};
+	if let Err(e) = append(log, &record) {
+		panic!("Error: While writing to the audit log {:?}: {e}", log.path);
+	}
+}
+
+/// Appends a record as one line. The line is written with a single write(2) in append mode, so that records from
+/// concurrent invocations are not interleaved.
+fn append(log: &AuditLog, record: &Record) -> io::Result<()> {
+	let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+	line.push('\n');
+	let mut f = File::options().create(true).append(true).open(&log.path)?;
+	f.write_all(line.as_bytes())
+}
+
+#[test]
+fn test_append() {
+	let dir = tempfile::tempdir().unwrap();
+	let log = AuditLog {
+		path: dir.path().join("audit.jsonl"),
+		command: "cg2util restrict grp memory.max=1G".to_string(),
+	};
+	let record = |action, key, value, effective: Option<&str>, result: &str| Record {
+		timestamp: "2026-10-16T08:30:00.000Z".to_string(),
+		command: &log.command,
+		cgroup: "/a/grp".to_string(),
+		action,
+		key,
+		value,
+		effective: effective.map(str::to_string),
+		result: result.to_string(),
+	};
+	append(&log, &record("create", None, None, None, "ok")).unwrap();
+	append(
+		&log,
+		&record("set", Some("memory.max"), Some("1G"), Some("1073741824"), "ok"),
+	)
+	.unwrap();
+	let lines = std::fs::read_to_string(&log.path).unwrap();
+	let lines = lines.lines().collect::<Vec<_>>();
+	assert_eq!(
+		lines,
+		[
+			r#"{"timestamp":"2026-10-16T08:30:00.000Z","command":"cg2util restrict grp memory.max=1G","cgroup":"/a/grp","action":"create","result":"ok"}"#,
+			r#"{"timestamp":"2026-10-16T08:30:00.000Z","command":"cg2util restrict grp memory.max=1G","cgroup":"/a/grp","action":"set","key":"memory.max","value":"1G","effective":"1073741824","result":"ok"}"#,
+		]
+	);
+}
@@ -14,35 +14,363 @@
 
 use cg2tools::internal;
 use cg2tools::CGroup;
+use clap::ArgGroup;
 use clap::Parser;
+use std::collections::hash_map::RandomState;
+use std::env;
 use std::ffi::OsString;
+use std::fs;
+use std::hash::BuildHasher;
+use std::hash::Hasher;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::process;
 use std::process::Command;
+use std::process::ExitStatus;
+use std::time::SystemTime;
 
 #[derive(Parser, Debug)]
 #[command(version, about = "Runs a program with a specific control group")]
+#[command(group(ArgGroup::new("undo").args(["transient", "ephemeral", "sticky"]).multiple(true)))]
 struct Cli {
-	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
-	#[arg()]
-	cgroup: String,
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
 
-	/// The subcommand to run.
-	#[arg()]
-	cmd: OsString,
+	/// The subcommand to run, followed by its arguments. Options after the subcommand are passed to it.
+	#[arg(required = true, trailing_var_arg(true), value_name = "CMD")]
+	command: Vec<OsString>,
 
-	/// Arguments to the subcommand.
-	#[arg(allow_hyphen_values(true))]
-	args: Vec<OsString>,
+	/// Write the process ID of the subcommand to this file while it runs, for use by process supervisors.
+	#[arg(long, value_name = "PATH")]
+	pid_file: Option<PathBuf>,
+
+	/// Resolve the control group relative to this systemd slice, such as "system.slice", instead of the control group of the current process. The control group must then be a relative name.
+	#[arg(long, value_name = "SLICE")]
+	slice: Option<String>,
+
+	/// Undo the changes to the control group when the subcommand exits: restore the restrictions changed by --set, or delete the control group if it did not exist yet.
+	#[arg(long)]
+	transient: bool,
+
+	/// Run the subcommand in a new control group with a unique name, such as "cg2exec-1234-5f3a9c1e", created under the control group argument, and delete it when the subcommand exits. Pass "." to create it under the control group of the current process.
+	#[arg(long)]
+	ephemeral: bool,
+
+	/// Like --ephemeral, but name the new control group after NAME and the process ID, such as "build-1234-5f3a9c1e", so that it is easy to tell which command it belongs to.
+	#[arg(long, value_name = "NAME", value_parser = parse_sticky_name, conflicts_with = "ephemeral")]
+	sticky: Option<String>,
+
+	/// Restriction to apply while the subcommand runs, in file=value format, such as "memory.max=1G". May be repeated.
+	#[arg(long, value_name = "KEY=VALUE", value_parser = internal::parse_key_value, requires = "undo")]
+	set: Vec<(String, String)>,
+
+	/// Append a JSON record of every change made to a control group to this file.
+	#[arg(long, value_name = "PATH")]
+	audit_log: Option<PathBuf>,
 }
 
 fn main() {
-	let args = Cli::parse();
+	let args = internal::parse_args::<Cli>(env::args_os()).unwrap_or_else(|e| e.exit());
 	internal::os_check(&args);
-	let mut cgroup = CGroup::current();
-	if cgroup.append(&args.cgroup) {
-		cgroup.classify_current();
+	match internal::Config::load() {
+		Ok(config) => {
+			if let Some(root) = config.root {
+				internal::set_configured_cgroupfs_root(root);
+			}
+		}
+		Err(e) => {
+			println!("Error: While loading the configuration: {e}");
+			process::exit(1);
+		}
+	}
+	if let Some(audit_log) = &args.audit_log {
+		internal::enable_audit_log(audit_log.clone());
+	}
+	// Signals stay pending from here on, so that the control group is cleaned up if this process is asked to stop
+	// while preparing it, and are forwarded to the subcommand once it runs
+	internal::block_termination_signals();
+	let original = CGroup::current();
+	let (mut cgroup, mut moved) = target_cgroup(&args, &original);
+	if args.ephemeral || args.sticky.is_some() {
+		cgroup = ephemeral_cgroup(&cgroup, args.sticky.as_deref().unwrap_or("cg2exec"));
+		moved = true;
+	}
+	let create = (args.transient || args.ephemeral || args.sticky.is_some()) && !cgroup_exists(&cgroup);
+	let mut undo = Undo {
+		original,
+		cgroup: cgroup.clone(),
+		created: false,
+		self_moved: false,
+		previous: Vec::new(),
+	};
+	// A failure while preparing the control group must not leave behind the changes made so far
+	let prepared = panic::catch_unwind(AssertUnwindSafe(|| prepare(&args, &cgroup, moved, create, &mut undo)));
+	let mut child = match prepared {
+		Ok(child) => child,
+		Err(payload) => {
+			undo.undo();
+			panic::resume_unwind(payload);
+		}
+	};
+	internal::forward_termination_signals(child.id());
+	if let Some(pid_file) = &args.pid_file {
+		if let Err(e) = internal::write_file_atomic(pid_file, &format!("{}\n", child.id())) {
+			println!("Error: While writing the PID file {pid_file:?}: {e}");
+			child.kill();
+			let _ = child.wait();
+			undo.undo();
+			std::process::exit(1);
+		}
+	}
+	let status = child.wait();
+	if let Some(pid_file) = &args.pid_file {
+		let _ = fs::remove_file(pid_file);
+	}
+	undo.undo();
+	// Like a shell, report a subcommand killed by a signal with 128 plus the signal number
+	std::process::exit(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)))
+}
+
+/// Performs the steps from [`plan`] and returns the running subcommand, recording in `undo` what has to be undone
+/// when cg2exec exits.
+fn prepare(args: &Cli, cgroup: &CGroup, moved: bool, create: bool, undo: &mut Undo) -> Child {
+	let mut child = None;
+	for step in plan(args, create) {
+		match step {
+			Step::Create => {
+				// Another process may have created the group in the meantime, in which case it is not ours to delete
+				undo.created = match cgroup.create() {
+					Ok(created) => created,
+					Err(e) => panic!("Error: While creating control group {cgroup}: {e}"),
+				};
+				if undo.created {
+					println!("Notice: Created control group {cgroup}");
+				}
+			}
+			Step::EnableControllers(keys) => {
+				cgroup.enable_controllers_for_restrictions(&keys);
+			}
+			Step::SetRestrictions(restrictions) => {
+				let mut changed = Vec::new();
+				for (key, value) in restrictions {
+					let current = match cgroup.get_restriction(&key) {
+						Ok(current) => current,
+						Err(e) => panic!("Error: While reading {key} of control group {cgroup}: {e}"),
+					};
+					// Only restore the restrictions that this invocation changes
+					if current != value {
+						undo.previous.push((key.clone(), current));
+						changed.push((key, value));
+					}
+				}
+				cgroup.set_restrictions(&changed);
+			}
+			Step::Run => {
+				let spawn = || match Command::new(&args.command[0]).args(&args.command[1..]).spawn() {
+					Ok(child) => Child::Spawned(child),
+					Err(e) => panic!(
+						"Error: While running {:?} in control group {cgroup}: {e}",
+						args.command[0]
+					),
+				};
+				// Start the subcommand directly in the control group if the kernel supports it, so that this process
+				// stays put
+				child = Some(if !moved {
+					spawn()
+				} else {
+					match cgroup.spawn_into(&args.command) {
+						Ok(pid) => Child::Cloned(pid),
+						Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+							cgroup.classify_current();
+							undo.self_moved = true;
+							spawn()
+						}
+						Err(e) => panic!(
+							"Error: While running {:?} in control group {cgroup}: {e}",
+							args.command[0]
+						),
+					}
+				});
+			}
+		}
+	}
+	child.expect("plan ends with Step::Run")
+}
+
+/// The changes that cg2exec made to the control group, which are undone when it exits.
+struct Undo {
+	/// The control group that this process started in.
+	original: CGroup,
+	/// The control group of the subcommand.
+	cgroup: CGroup,
+	/// Whether this invocation created the control group, in which case it is deleted.
+	created: bool,
+	/// Whether this process moved itself into the control group.
+	self_moved: bool,
+	/// The restrictions changed by --set, with their previous values, which are restored if the control group is kept.
+	previous: Vec<(String, String)>,
+}
+
+impl Undo {
+	/// Deletes the control group if this invocation created it, or else restores the restrictions that it changed.
+	fn undo(&self) {
+		// A control group with processes cannot be removed, so leave it first
+		if self.self_moved {
+			self.original.classify_current();
+		}
+		if self.created {
+			if let Err(e) = self.cgroup.remove() {
+				println!("Warning: Could not delete transient control group {}: {e}", self.cgroup);
+			}
+		} else {
+			self.cgroup.set_restrictions(&self.previous);
+		}
+	}
+}
+
+/// A step of preparing the control group and running the subcommand.
+#[derive(Debug, PartialEq, Eq)]
+enum Step {
+	/// Create the control group, which must exist before anything else.
+	Create,
+	/// Enable the controllers of the --set restrictions in the ancestors' `cgroup.subtree_control`, so that the
+	/// restriction files exist.
+	EnableControllers(Vec<String>),
+	/// Write the --set restrictions, which must happen before the subcommand starts so that it never runs
+	/// unrestricted.
+	SetRestrictions(Vec<(String, String)>),
+	/// Start the subcommand in the control group, either directly or by moving this process into the group first.
+	Run,
+}
+
+/// Returns the steps to perform, in order: create the control group (only if `create` is true), enable the required
+/// controllers, set the restrictions, and finally run the subcommand.
+fn plan(args: &Cli, create: bool) -> Vec<Step> {
+	let mut steps = Vec::new();
+	if create {
+		steps.push(Step::Create);
+	}
+	if !args.set.is_empty() {
+		steps.push(Step::EnableControllers(
+			args.set.iter().map(|(key, _)| key.clone()).collect(),
+		));
+		steps.push(Step::SetRestrictions(args.set.clone()));
+	}
+	steps.push(Step::Run);
+	steps
+}
+
+/// A running subcommand.
+enum Child {
+	/// Started in its control group with [`CGroup::spawn_into`].
+	Cloned(u32),
+	/// Started with [`Command`] after this process moved into the control group.
+	Spawned(process::Child),
+}
+
+impl Child {
+	fn id(&self) -> u32 {
+		match self {
+			Self::Cloned(pid) => *pid,
+			Self::Spawned(child) => child.id(),
+		}
+	}
+
+	fn kill(&mut self) {
+		match self {
+			// SAFETY: kill has no memory safety requirements
+			Self::Cloned(pid) => unsafe {
+				libc::kill(*pid as libc::pid_t, libc::SIGKILL);
+			},
+			Self::Spawned(child) => {
+				let _ = child.kill();
+			}
+		}
+	}
+
+	/// Waits for the subcommand to exit and returns its exit status.
+	fn wait(&mut self) -> ExitStatus {
+		match self {
+			Self::Cloned(pid) => internal::wait_pid(*pid).unwrap(),
+			Self::Spawned(child) => child.wait().unwrap(),
+		}
+	}
+}
+
+/// Returns the control group named by the arguments, relative to --slice or else to `original`, and whether it differs
+/// from `original`, in which case the subcommand has to be started in it.
+fn target_cgroup(args: &Cli, original: &CGroup) -> (CGroup, bool) {
+	let mut cgroup = match &args.slice {
+		Some(slice) => slice_cgroup(slice, args.cgroup.name()),
+		None => original.clone(),
+	};
+	cgroup.append(args.cgroup.name());
+	// Compare the resolved names, since the paths differ in "." and ".." components even for the same group
+	let moved = cgroup.canonical_name() != original.canonical_name();
+	(cgroup, moved)
+}
+
+/// Returns whether the control group exists, exiting with an error if that cannot be determined.
+fn cgroup_exists(cgroup: &CGroup) -> bool {
+	match cgroup.exists() {
+		Ok(exists) => exists,
+		Err(e) => panic!("Error: While checking whether control group {cgroup} exists: {e}"),
+	}
+}
+
+/// Returns a child of `base` with a name from [`ephemeral_name`] that does not exist yet.
+fn ephemeral_cgroup(base: &CGroup, prefix: &str) -> CGroup {
+	loop {
+		let mut cgroup = base.clone();
+		cgroup.append(ephemeral_name(prefix));
+		if !cgroup_exists(&cgroup) {
+			return cgroup;
+		}
+	}
+}
+
+/// Returns a name for an ephemeral control group made of `prefix`, the process ID and 32 random bits, such as
+/// "cg2exec-1234-5f3a9c1e".
+fn ephemeral_name(prefix: &str) -> String {
+	// RandomState is seeded with random keys, so hashing the current time gives a different value in every call
+	let mut hasher = RandomState::new().build_hasher();
+	if let Ok(elapsed) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+		hasher.write_u128(elapsed.as_nanos());
+	}
+	format!("{prefix}-{}-{:08x}", process::id(), hasher.finish() as u32)
+}
+
+/// Parses the name passed to --sticky, which becomes the start of a single path component.
+fn parse_sticky_name(input: &str) -> Result<String, &'static str> {
+	if input.is_empty() || input == "." || input == ".." {
+		return Err("expected a name for the control group, such as \"build\"");
+	}
+	if input.contains('/') {
+		return Err("name must not contain \"/\"; pass the parent control group as the first argument instead");
+	}
+	Ok(input.to_string())
+}
+
+/// Resolves a systemd slice to its control group, checking that the requested control group is relative to it.
+fn slice_cgroup(slice: &str, cgroup: &str) -> CGroup {
+	if !slice.ends_with(".slice") {
+		println!("Error: --slice expects a slice unit ending in \".slice\", such as system.slice; got \"{slice}\"");
+		std::process::exit(1);
+	}
+	if cgroup.starts_with('/') {
+		println!("Error: Control group {cgroup} is absolute, so it cannot be placed under --slice {slice}. Pass a relative name instead.");
+		std::process::exit(1);
+	}
+	match CGroup::from_systemd_unit(slice) {
+		Ok(cgroup) => cgroup,
+		Err(e) => {
+			println!("Error: Invalid slice: {e}");
+			std::process::exit(1);
+		}
 	}
-	let status = Command::new(&args.cmd).args(&args.args).status().unwrap();
-	std::process::exit(status.code().unwrap_or(0))
 }
 
 #[test]
@@ -57,4 +385,69 @@ fn test_cli() {
 	insta::assert_debug_snapshot!(cli("cg2exec --flag grp cmd"));
 	insta::assert_debug_snapshot!(cli("cg2exec grp --flag cmd"));
 	insta::assert_debug_snapshot!(cli("cg2exec grp cmd --flag"));
+	insta::assert_debug_snapshot!(cli("cg2exec --pid-file /run/job.pid grp cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec grp cmd --pid-file /run/job.pid"));
+	insta::assert_debug_snapshot!(cli(
+		"cg2exec --transient grp --set memory.max=1G --set cpu.max=50000 cmd"
+	));
+	insta::assert_debug_snapshot!(cli("cg2exec grp --set memory.max=1G cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --transient grp --set memory cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --slice batch.slice grp cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --audit-log /var/log/cg2tools.jsonl grp cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --ephemeral . --set memory.max=1G cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --ephemeral --transient sandbox cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --sticky build . --set memory.max=1G cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --sticky build --ephemeral . cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --sticky a/b . cmd"));
+}
+
+#[test]
+fn test_cli_cgroup_flag() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		let args = shlex::split(input).unwrap().into_iter().map(OsString::from);
+		internal::parse_args::<Cli>(args).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2exec --cgroup grp cmd --cgroup extra"));
+	insta::assert_debug_snapshot!(cli("cg2exec --transient --set memory.max=1G --cgroup=grp cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --cgroup grp cmd -h"));
+	insta::assert_debug_snapshot!(cli("cg2exec grp --cgroup other cmd"));
+	insta::assert_debug_snapshot!(cli("cg2exec --cgroup grp"));
+}
+
+#[test]
+fn test_plan() {
+	fn plan_for(input: &str, create: bool) -> Vec<Step> {
+		plan(&Cli::try_parse_from(shlex::split(input).unwrap()).unwrap(), create)
+	}
+	insta::assert_debug_snapshot!(plan_for("cg2exec grp cmd", false));
+	insta::assert_debug_snapshot!(plan_for("cg2exec --transient grp cmd", true));
+	insta::assert_debug_snapshot!(plan_for(
+		"cg2exec --transient grp --set memory.max=1G --set cpu.max=50000 cmd",
+		true
+	));
+	insta::assert_debug_snapshot!(plan_for("cg2exec --transient grp --set memory.max=1G cmd", false));
+}
+
+#[test]
+fn test_target_cgroup() {
+	fn target_for(input: &str) -> (String, bool) {
+		let original = CGroup::from_cgroup_path("/user.slice/session-1.scope");
+		let (cgroup, moved) = target_cgroup(&Cli::try_parse_from(shlex::split(input).unwrap()).unwrap(), &original);
+		(cgroup.canonical_name(), moved)
+	}
+	assert_eq!(target_for("cg2exec . cmd"), ("/user.slice/session-1.scope".to_string(), false));
+	assert_eq!(target_for("cg2exec ./ cmd"), ("/user.slice/session-1.scope".to_string(), false));
+	assert_eq!(target_for("cg2exec job cmd"), ("/user.slice/session-1.scope/job".to_string(), true));
+	assert!(target_for("cg2exec --slice batch.slice . cmd").1);
+	assert!(target_for("cg2exec --slice batch.slice job cmd").1);
+}
+
+#[test]
+fn test_ephemeral_name() {
+	let name = ephemeral_name("cg2exec");
+	let suffix = name.strip_prefix(&format!("cg2exec-{}-", process::id())).unwrap();
+	assert_eq!(suffix.len(), 8);
+	assert!(suffix.chars().all(|c| c.is_ascii_hexdigit()));
+	assert_ne!(ephemeral_name("cg2exec"), name);
+	assert!(ephemeral_name("build").starts_with(&format!("build-{}-", process::id())));
 }
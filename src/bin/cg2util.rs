@@ -14,44 +14,129 @@
 
 use cg2tools::internal;
 use cg2tools::CGroup;
+use cg2tools::CGroupError;
+use cg2tools::DeviceNumber;
+use cg2tools::Limit;
+use cg2tools::Profile;
+use cg2tools::SetOutcome;
+use clap::ArgGroup;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::any::Any;
+use std::collections::HashSet;
+use std::env;
+use std::os::fd::RawFd;
+use std::panic;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(version, about = "Manipulates settings for unified control groups (cgroups v2)")]
 struct Cli {
+	/// How to print errors: as prose (the default), or as one JSON object per error on stderr
+	#[arg(long, global = true, value_enum)]
+	error_format: Option<ErrorFormat>,
+
+	/// When to color the output of tree and status: "auto" (the default) colors it if stdout is a terminal and NO_COLOR is not set
+	#[arg(long, global = true, value_enum)]
+	color: Option<ColorChoice>,
+
+	/// Append a JSON record of every change made to a control group to this file
+	#[arg(long, global = true, value_name = "PATH")]
+	audit_log: Option<PathBuf>,
+
+	/// Fail reads and writes of control group files that take longer than this, such as "5s", instead of waiting indefinitely on a hung file system
+	#[arg(long, global = true, value_name = "DURATION", value_parser = internal::parse_duration)]
+	io_timeout: Option<Duration>,
+
+	/// Print the changes that the command would make to control groups, such as "Would write \"+cpu\" to /sys/fs/cgroup/a/cgroup.subtree_control", without making them
+	#[arg(long, global = true)]
+	dry_run: bool,
+
 	#[command(subcommand)]
 	command: Command,
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ErrorFormat {
+	Text,
+	Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+	Text,
+	Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ColorChoice {
+	Auto,
+	Always,
+	Never,
+}
+
 #[derive(Args, Debug)]
 struct CreateCommand {
-	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
-	#[arg()]
-	cgroup: String,
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Profile to apply after creating the control group: a name in ~/.config/cg2tools/profiles or /etc/cg2tools/profiles, or a path to a profile file.
+	#[arg(long, value_name = "PROFILE")]
+	template: Option<String>,
+
+	/// Apply the template even if the control group already exists with different settings.
+	#[arg(long, requires = "template")]
+	force: bool,
+
+	/// Controllers to enable for children of every control group created above the new one, such as "cpu,memory", so that the new control group can use them right away.
+	#[arg(long, value_delimiter = ',', value_name = "CONTROLLERS")]
+	enable: Vec<String>,
 }
 
 #[derive(Args, Debug)]
 struct ClassifyCommand {
-	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
-	#[arg()]
-	cgroup: String,
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
 
 	/// Process IDs to reclassify.
-	#[arg(value_delimiter = ',', required = true)]
+	#[arg(value_delimiter = ',', required_unless_present_any = ["match_name", "pidfd"])]
 	pids: Vec<u32>,
 
+	/// Also reclassify the processes referred to by these inherited pidfds, which cannot be confused by a reused process ID. Requires Linux 5.5 or newer.
+	#[arg(long, value_delimiter = ',', value_name = "FD")]
+	pidfd: Vec<RawFd>,
+
+	/// Also reclassify the processes whose name contains this string. The kernel truncates process names to 15 characters.
+	#[arg(long, value_name = "SUBSTR")]
+	match_name: Option<String>,
+
+	/// Process IDs to leave alone when matching by name.
+	#[arg(long, value_delimiter = ',', value_name = "PID", requires = "match_name")]
+	exclude: Vec<u32>,
+
 	/// Create the control group if it doesn't exist yet.
 	#[arg(long)]
 	auto: bool,
+
+	/// Check that each process is listed in the control group after moving it, and fail if the kernel did not move it.
+	#[arg(long)]
+	verify: bool,
+
+	/// Also reclassify the descendants of each process (its children, their children, and so on).
+	#[arg(long)]
+	with_descendants: bool,
 }
 
 #[derive(Args, Debug)]
 struct ControlCommand {
-	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
-	#[arg()]
-	cgroup: String,
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
 
 	#[command(flatten)]
 	control: ControlList,
@@ -59,45 +144,112 @@ struct ControlCommand {
 	/// Create the control group if it doesn't exist yet.
 	#[arg(long)]
 	auto: bool,
+
+	/// Hidden alias of --format json, kept for existing scripts.
+	#[arg(long, hide = true, conflicts_with_all = ["controllers", "inherit", "inherit_parent"])]
+	json: bool,
+
+	/// When listing controllers, print them as prose (the default) or as a JSON object.
+	#[arg(
+		long,
+		value_enum,
+		conflicts_with_all = ["controllers", "inherit", "inherit_parent", "json"],
+		default_value_if("json", "true", "json")
+	)]
+	format: Option<OutputFormat>,
 }
 
 #[derive(Args, Debug)]
 #[group(multiple = false)]
 struct ControlList {
-	/// List of control to enable in the new control group.
+	/// Controllers to enable for the control group, such as "+cpu", or to disable, such as "-cpu". Disabling a controller for a control group disables it for all children of its parent.
 	#[arg(value_delimiter = ',', allow_hyphen_values(true), value_parser = parse_controller_flag)]
 	controllers: Vec<ControllerFlag>,
 
-	/// Inherit all control from the specified control group, relative to the control group of the current process.
+	/// Inherit all control from the specified control group. May be relative to the control group of the current process, including ".." components such as "../sibling", or absolute.
 	#[arg(long, value_name = "CGROUP")]
 	inherit: Option<String>,
+
+	/// Enable for children of the control group the same controllers that its parent enables for children.
+	#[arg(long)]
+	inherit_parent: bool,
 }
 
 #[derive(Debug, Clone)]
 struct ControllerFlag {
 	pub name: String,
-	pub _enable: bool,
+	pub enable: bool,
+}
+
+/// Returns the line for `cpu.max` that allows `percent` of one CPU in each `period`: the quota and the period in
+/// microseconds, such as "150000 100000" for 150% of 100ms. The quota is rounded to the nearest microsecond.
+fn cpu_max_line(percent: f64, period: Duration) -> String {
+	let period_us = period.as_micros() as u64;
+	let quota_us = (percent / 100.0 * period_us as f64).round() as u64;
+	format!("{quota_us} {period_us}")
+}
+
+/// Parses the percentage for `cg2util cpu --percent`, which must be positive.
+fn parse_cpu_percent(input: &str) -> Result<f64, &'static str> {
+	match input.parse::<f64>() {
+		Ok(percent) if percent.is_finite() && percent > 0.0 => Ok(percent),
+		_ => Err("expected a positive percentage, such as 50 or 150"),
+	}
+}
+
+/// Parses the period for `cg2util cpu --period`, which the kernel limits to between 1ms and 1s.
+fn parse_cpu_period(input: &str) -> Result<Duration, &'static str> {
+	let period = internal::parse_duration(input)?;
+	if period < Duration::from_millis(1) || period > Duration::from_secs(1) {
+		return Err("the period must be between 1ms and 1s");
+	}
+	Ok(period)
+}
+
+/// Parses a block device given by its device number, such as "8:0", or its path, such as "/dev/sda".
+fn parse_device(input: &str) -> Result<DeviceNumber, String> {
+	if let Ok(device) = input.parse() {
+		return Ok(device);
+	}
+	DeviceNumber::from_path(input).map_err(|e| format!("{input}: {e}"))
 }
 
 fn parse_controller_flag(input: &str) -> Result<ControllerFlag, &'static str> {
-	if let Some(name) = input.strip_prefix('+') {
-		Ok(ControllerFlag {
-			name: name.to_string(),
-			_enable: true,
-		})
+	let (enable, name) = if let Some(name) = input.strip_prefix('+') {
+		(true, name)
+	} else if let Some(name) = input.strip_prefix('-') {
+		(false, name)
 	} else {
-		Err("controllers may only be enabled for now. Pass them with +, as in: +cpu +memory")
+		return Err("expected a controller with + to enable it or - to disable it, as in: +cpu -memory");
+	};
+	// Also rejects options such as --auto after the controllers
+	if name.is_empty() || !name.chars().all(|c| matches!(c, '_' | 'a'..='z')) {
+		return Err("expected a controller with + to enable it or - to disable it, as in: +cpu -memory");
 	}
+	Ok(ControllerFlag {
+		name: name.to_string(),
+		enable,
+	})
+}
+
+/// Output of `cg2util control` when listing controllers.
+#[derive(Serialize, Debug)]
+struct ControllerListOutput {
+	/// Path of the control group relative to the cgroupfs root.
+	cgroup: String,
+	/// Controllers available in the control group.
+	controllers: Vec<String>,
+	/// Controllers enabled for children of the control group.
+	subtree_control: Vec<String>,
 }
 
 #[derive(Args, Debug)]
 struct RestrictCommand {
-	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
-	#[arg()]
-	cgroup: String,
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
 
-	/// Restrictions to apply in file=value format, such as "cpu.weight=150". See <https://docs.kernel.org/admin-guide/cgroup-v2.html>
-	#[arg(value_parser = parse_key_value, required = true)]
+	/// Restrictions to apply in file=value format, such as "cpu.weight=150". Each argument is one restriction: commas and spaces are part of the value, as in "cpuset.cpus=0-3,8" or "io.max=8:16 rbps=max" (quoted). See <https://docs.kernel.org/admin-guide/cgroup-v2.html>
+	#[arg(value_parser = internal::parse_key_value, required = true)]
 	restrictions: Vec<(String, String)>,
 
 	/// Create the control group if it doesn't exist yet and enable the required controllers if they aren't enabled yet.
@@ -105,15 +257,385 @@ struct RestrictCommand {
 	auto: bool,
 }
 
-fn parse_key_value(input: &str) -> Result<(String, String), &'static str> {
-	let (key, value) = input.split_once('=').ok_or("expected key=value")?;
-	if !key.chars().all(|c| matches!(c, '_' | '.' | 'a'..='z')) {
-		return Err("key contains invalid characters");
+#[derive(Args, Debug)]
+struct WatchMemoryCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Number of seconds between readings.
+	#[arg(long, value_name = "SECONDS", default_value_t = 1)]
+	interval: u64,
+
+	/// Print byte values with IEC suffixes, such as "1.8GiB", instead of raw numbers.
+	#[arg(long, short = 'H')]
+	human: bool,
+}
+
+#[derive(Args, Debug)]
+struct TreeCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Number of threads for reading the hierarchy, which can help with very large hierarchies.
+	#[arg(long, short, default_value_t = 1)]
+	jobs: usize,
+
+	/// Show how long ago each control group was created.
+	#[arg(long)]
+	age: bool,
+}
+
+#[derive(Args, Debug)]
+struct GcCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Only delete control groups at least this old, such as "30m", "12h", or "7d".
+	#[arg(long, value_name = "DURATION", value_parser = internal::parse_duration)]
+	older_than: Duration,
+}
+
+#[derive(Args, Debug)]
+struct ResetCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Also reset all descendants, starting from the leaves.
+	#[arg(long)]
+	recursive: bool,
+
+	/// Reset control groups even if they have processes or contain this process.
+	#[arg(long)]
+	force: bool,
+}
+
+#[derive(Args, Debug)]
+struct ReclaimCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Number of bytes to reclaim. The kernel may reclaim less.
+	#[arg()]
+	bytes: u64,
+}
+
+#[derive(Args, Debug)]
+struct NumaStatCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Print the breakdown as JSON, keyed by node and then by category.
+	#[arg(long)]
+	json: bool,
+}
+
+#[derive(Args, Debug)]
+struct StatusCommand {
+	#[command(flatten)]
+	cgroups: internal::CGroupArgs,
+
+	/// Print the status of all control groups as a JSON array.
+	#[arg(long)]
+	json: bool,
+}
+
+#[derive(Args, Debug)]
+struct PrepareForCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Restrictions that will be set, such as "cpu.max" or "memory.max".
+	#[arg(required = true)]
+	keys: Vec<String>,
+
+	/// Create the control group if it doesn't exist yet.
+	#[arg(long)]
+	auto: bool,
+}
+
+#[derive(Args, Debug)]
+struct BoostCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Temporary value in file=value format, such as "memory.high=4G". The file must hold a single number or "max".
+	#[arg(value_parser = internal::parse_key_value)]
+	restriction: (String, String),
+
+	/// How long to keep the temporary value, such as "30s" or "5m".
+	#[arg(long = "for", value_name = "DURATION", value_parser = internal::parse_duration)]
+	duration: Duration,
+}
+
+#[derive(Args, Debug)]
+struct SharesCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+}
+
+#[derive(Args, Debug)]
+struct ResolveCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+}
+
+#[derive(Args, Debug)]
+struct CheckProfileCommand {
+	/// Profile to check: a name in ~/.config/cg2tools/profiles or /etc/cg2tools/profiles, or a path to a profile file.
+	#[arg()]
+	profile: String,
+}
+
+#[derive(Args, Debug)]
+struct ChainCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Controller to follow, such as "cpu".
+	#[arg()]
+	controller: String,
+}
+
+#[derive(Args, Debug)]
+struct FreezeCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Wait until the kernel reports the new state in cgroup.events.
+	#[arg(long)]
+	wait: bool,
+
+	/// How long to wait with --wait, such as "5s" or "1m".
+	#[arg(long, value_name = "DURATION", value_parser = internal::parse_duration, default_value = "5s", requires = "wait")]
+	timeout: Duration,
+
+	/// Freeze the control group even if it contains this process.
+	#[arg(long)]
+	force: bool,
+}
+
+#[derive(Args, Debug)]
+struct KillCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Kill the processes even if the control group contains this process.
+	#[arg(long)]
+	force: bool,
+}
+
+#[derive(Args, Debug)]
+struct PsCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Also list the processes of all descendants.
+	#[arg(long)]
+	recursive: bool,
+}
+
+#[derive(Args, Debug)]
+struct StatCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+}
+
+#[derive(Args, Debug)]
+struct TypeCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Turn the control group into a member of a threaded subtree instead of printing its type. This cannot be undone.
+	#[arg(long)]
+	threaded: bool,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("limits").args(["rbps", "wbps", "riops", "wiops"]).multiple(true).required(true)))]
+struct IoCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Block device to limit, given by its path, such as "/dev/sda", or its device number, such as "8:0".
+	#[arg(long, value_name = "DEVICE", value_parser = parse_device)]
+	device: DeviceNumber,
+
+	/// Maximum bytes read per second, with an optional binary suffix, such as "1M" or "512k", or "max" for no limit.
+	#[arg(long, value_name = "SIZE", value_parser = internal::parse_size)]
+	rbps: Option<Limit>,
+
+	/// Maximum bytes written per second, with an optional binary suffix, such as "1M" or "512k", or "max" for no limit.
+	#[arg(long, value_name = "SIZE", value_parser = internal::parse_size)]
+	wbps: Option<Limit>,
+
+	/// Maximum read operations per second, or "max" for no limit.
+	#[arg(long, value_name = "COUNT")]
+	riops: Option<Limit>,
+
+	/// Maximum write operations per second, or "max" for no limit.
+	#[arg(long, value_name = "COUNT")]
+	wiops: Option<Limit>,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("limits").args(["high", "max", "swap_max"]).multiple(true).required(true)))]
+struct MemoryCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Memory usage above which the processes are throttled and reclaimed from, written to memory.high. Sizes take an optional binary suffix, such as "512M" or "1Gi", or are "max" for no limit.
+	#[arg(long, value_name = "SIZE", value_parser = internal::parse_size)]
+	high: Option<Limit>,
+
+	/// Hard memory limit, written to memory.max. Processes are killed by the OOM killer if they cannot stay below it.
+	#[arg(long, value_name = "SIZE", value_parser = internal::parse_size)]
+	max: Option<Limit>,
+
+	/// Swap limit, written to memory.swap.max. Pass 0 to disable swap for the control group.
+	#[arg(long, value_name = "SIZE", value_parser = internal::parse_size)]
+	swap_max: Option<Limit>,
+}
+
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("settings").args(["percent", "weight"]).multiple(true).required(true)))]
+struct CpuCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Maximum CPU time per period as a percentage of one CPU, written to cpu.max. Values above 100 allow more than one CPU, such as 150 for one and a half.
+	#[arg(long, value_parser = parse_cpu_percent)]
+	percent: Option<f64>,
+
+	/// Length of the period that --percent applies to, between 1ms and 1s.
+	#[arg(long, value_name = "DURATION", value_parser = parse_cpu_period, default_value = "100ms", requires = "percent")]
+	period: Duration,
+
+	/// Proportional share of CPU time relative to the siblings, written to cpu.weight.
+	#[arg(long, value_parser = clap::value_parser!(u64).range(1..=10000))]
+	weight: Option<u64>,
+}
+
+#[derive(Args, Debug)]
+struct ExistsCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+}
+
+#[derive(Args, Debug)]
+struct GetCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Restriction to read, such as "cpu.weight".
+	#[arg()]
+	key: String,
+}
+
+#[derive(Args, Debug)]
+struct DeleteCommand {
+	#[command(flatten)]
+	cgroup: internal::CGroupArg,
+
+	/// Also delete all descendants, starting from the leaves.
+	#[arg(long)]
+	recursive: bool,
+
+	/// Try to delete the control group even if it contains this process.
+	#[arg(long)]
+	force: bool,
+}
+
+/// Output of `cg2util status` for one control group.
+#[derive(Serialize, Debug)]
+struct StatusOutput {
+	/// Path of the control group relative to the cgroupfs root.
+	cgroup: String,
+	/// Whether the control group or any of its descendants has processes.
+	populated: bool,
+	/// Whether the control group is frozen.
+	frozen: bool,
+	/// Number of live descendant control groups.
+	nr_descendants: u64,
+	/// Controllers available in the control group.
+	controllers: Vec<String>,
+	/// Controllers enabled for children of the control group.
+	subtree_control: Vec<String>,
+	/// Memory usage in bytes, if the memory controller is enabled.
+	memory_current: Option<u64>,
+	/// Hard memory limit, if the memory controller is enabled, for the text output only.
+	#[serde(skip)]
+	memory_max: Option<Limit>,
+	/// Prose summary from [`CGroup::describe`], for the text output only.
+	#[serde(skip)]
+	summary: String,
+}
+
+impl StatusOutput {
+	fn read(cgroup: &CGroup) -> Self {
+		let controllers = cgroup.controllers();
+		// The root has no cgroup.events, cgroup.freeze, or memory.current. It holds this process in its subtree and
+		// cannot be frozen.
+		let is_root = cgroup.is_root();
+		let has_memory = !is_root && controllers.iter().any(|controller| controller == "memory");
+		let memory_current = has_memory.then(|| cgroup.memory_current());
+		let memory_max = has_memory.then(|| cgroup.memory_max());
+		StatusOutput {
+			cgroup: cgroup.canonical_name(),
+			populated: is_root || cgroup.is_populated(),
+			frozen: !is_root && cgroup.is_frozen(),
+			nr_descendants: cgroup.nr_descendants(),
+			controllers,
+			subtree_control: cgroup.subtree_controllers(),
+			memory_current,
+			memory_max,
+			summary: cgroup.describe(),
+		}
+	}
+
+	/// Returns whether the memory usage is at least 90% of `memory.max`.
+	fn is_near_memory_limit(&self) -> bool {
+		match (self.memory_current, self.memory_max) {
+			(Some(current), Some(Limit::Value(max))) => current as f64 >= max as f64 * 0.9,
+			_ => false,
+		}
 	}
-	if !key.contains('.') {
-		return Err("key must be of the form CONTROLLER.RESTRICTION");
+
+	/// Formats the status as a labeled block of lines. With `color`, the name and states are colored like in
+	/// `cg2util tree`, controller names in yellow, and the memory usage in red when it is near `memory.max`.
+	fn to_text(&self, color: bool) -> String {
+		let name_color = if self.frozen {
+			Some(internal::Color::Cyan)
+		} else if self.populated {
+			Some(internal::Color::Green)
+		} else {
+			None
+		};
+		let name = match name_color {
+			Some(name_color) => internal::paint(&self.cgroup, name_color, color),
+			None => self.cgroup.clone(),
+		};
+		let state = |value: bool, state_color| internal::paint(&value.to_string(), state_color, color && value);
+		let controllers = |controllers: &[String]| {
+			controllers
+				.iter()
+				.map(|controller| internal::paint(controller, internal::Color::Yellow, color))
+				.collect::<Vec<_>>()
+				.join(" ")
+		};
+		let mut text = format!("Control group {name}:\n");
+		text += &format!("  populated: {}\n", state(self.populated, internal::Color::Green));
+		text += &format!("  frozen: {}\n", state(self.frozen, internal::Color::Cyan));
+		text += &format!("  descendants: {}\n", self.nr_descendants);
+		text += &format!("  controllers: {}\n", controllers(&self.controllers));
+		text += &format!("  subtree_control: {}\n", controllers(&self.subtree_control));
+		if let Some(memory_current) = self.memory_current {
+			let near_limit = color && self.is_near_memory_limit();
+			let memory_current = internal::paint(&memory_current.to_string(), internal::Color::Red, near_limit);
+			text += &format!("  memory.current: {memory_current}\n");
+		}
+		text += &format!("  summary: {}\n", self.summary);
+		text
 	}
-	Ok((key.to_string(), value.to_string()))
 }
 
 #[derive(Subcommand, Debug)]
@@ -126,24 +648,172 @@ enum Command {
 	Control(ControlCommand),
 	/// Sets restrictions in a control group
 	Restrict(RestrictCommand),
+	/// Periodically prints the memory usage of a control group and whether it is being throttled
+	WatchMemory(WatchMemoryCommand),
+	/// Lists a control group and its descendants
+	Tree(TreeCommand),
+	/// Deletes old, empty descendants of a control group
+	Gc(GcCommand),
+	/// Resets the restrictions of a control group to their defaults
+	Reset(ResetCommand),
+	/// Asks the kernel to reclaim memory from a control group without changing its limits
+	Reclaim(ReclaimCommand),
+	/// Prints the memory usage of a control group per NUMA node
+	NumaStat(NumaStatCommand),
+	/// Prints a summary of the state of one or more control groups
+	Status(StatusCommand),
+	/// Enables the controllers needed to set the given restrictions in a control group
+	PrepareFor(PrepareForCommand),
+	/// Temporarily changes a limit of a control group, such as raising memory.high during a burst, then restores it
+	Boost(BoostCommand),
+	/// Shows the proportional share of CPU time that each child of a control group gets from its cpu.weight
+	Shares(SharesCommand),
+	/// Prints the absolute name of a control group and its directory in the cgroup file system, without changing anything
+	Resolve(ResolveCommand),
+	/// Checks a profile for syntax errors, unavailable controllers, and invalid values without applying it
+	CheckProfile(CheckProfileCommand),
+	/// Shows at each level from the root down to a control group whether a controller is enabled for children, to find where delegation breaks
+	Chain(ChainCommand),
+	/// Deletes an empty control group
+	Delete(DeleteCommand),
+	/// Prints the current value of a restriction, as reported by the kernel
+	Get(GetCommand),
+	/// Suspends all processes in a control group and its descendants
+	Freeze(FreezeCommand),
+	/// Resumes the processes in a control group suspended by freeze
+	Thaw(FreezeCommand),
+	/// Kills all processes in a control group and its descendants
+	Kill(KillCommand),
+	/// Prints the number of descendants of a control group, including those that were deleted but still linger in the kernel
+	Stat(StatCommand),
+	/// Lists the IDs of the processes in a control group, one per line
+	Ps(PsCommand),
+	/// Prints the type of a control group, such as "domain" or "threaded", or makes it threaded
+	Type(TypeCommand),
+	/// Limits the bandwidth or operations per second of a control group on a block device, keeping any limits not given
+	Io(IoCommand),
+	/// Sets the memory limits of a control group, with sizes such as "512M" or "1G"
+	Memory(MemoryCommand),
+	/// Sets the CPU bandwidth limit of a control group as a percentage of one CPU, or its CPU weight
+	Cpu(CpuCommand),
+	/// Exits with status 0 if a control group exists, 1 if it does not, or 2 if that cannot be determined, without printing anything
+	Exists(ExistsCommand),
 }
 
 fn main() {
-	let args = Cli::parse();
+	let args = internal::parse_args::<Cli>(env::args_os()).unwrap_or_else(|e| e.exit());
 	internal::os_check(&args);
-	let mut cgroup = CGroup::current();
+	let config = match internal::Config::load() {
+		Ok(config) => config,
+		Err(e) => {
+			println!("Error: While loading the configuration: {e}");
+			process::exit(1);
+		}
+	};
+	if let Some(root) = config.root.clone() {
+		internal::set_configured_cgroupfs_root(root);
+	}
+	let error_format = args
+		.error_format
+		.or_else(|| config_value(&config.error_format, "error_format"))
+		.unwrap_or(ErrorFormat::Text);
+	if let ErrorFormat::Json = error_format {
+		internal::enable_structured_errors();
+		panic::set_hook(Box::new(|info| {
+			eprintln!("{}", error_json(info.payload()));
+			process::exit(1);
+		}));
+	}
+	// NO_COLOR only overrides the configuration, not an explicit --color
+	let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+	let color = args
+		.color
+		.or_else(|| no_color.then_some(ColorChoice::Never))
+		.or_else(|| config_value(&config.color, "color"))
+		.unwrap_or(ColorChoice::Auto);
+	let color = match color {
+		ColorChoice::Auto => internal::stdout_supports_color(),
+		ColorChoice::Always => true,
+		ColorChoice::Never => false,
+	};
+	let config_auto = config.auto.unwrap_or(false);
+	if let Some(timeout) = args.io_timeout {
+		internal::set_io_timeout(timeout);
+	}
+	if let Some(audit_log) = args.audit_log {
+		internal::enable_audit_log(audit_log);
+	}
+	let mut cgroup = CGroup::current().with_dry_run(args.dry_run);
 	match args.command {
 		Command::Create(cmd_args) => {
-			cgroup.append(&cmd_args.cgroup);
-			cgroup.create();
+			cgroup.append(cmd_args.cgroup.name());
+			let profile = cmd_args.template.map(|template| {
+				let profile = match Profile::load(&template) {
+					Ok(profile) => profile,
+					Err(e) => panic!("Error: While loading profile {template}: {e}"),
+				};
+				let conflicts = cgroup.profile_conflicts(&profile);
+				if !conflicts.is_empty() && !cmd_args.force {
+					let conflicts = conflicts
+						.iter()
+						.map(|(key, current, wanted)| format!("{key} is \"{current}\" but {template} sets \"{wanted}\""))
+						.collect::<Vec<_>>()
+						.join("; ");
+					panic!("Error: Control group {cgroup} already exists with different settings: {conflicts}. Pass --force to apply the template anyway.");
+				}
+				profile
+			});
+			let configured = match cgroup.create_with_subtree_control(&cmd_args.enable, report_created) {
+				Ok(configured) => configured,
+				Err(e) => internal::exit_with_error(&format!("Error: While creating control group {cgroup}: {e}")),
+			};
+			for (level, outcomes) in configured {
+				println!("Controllers in {level}: {}", enable_summary(&outcomes));
+			}
+			if let Some(profile) = profile {
+				cgroup.apply_profile(&profile);
+			}
 		}
 		Command::Classify(cmd_args) => {
-			cgroup.append(&cmd_args.cgroup);
-			if cmd_args.auto {
-				cgroup.create();
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
+			}
+			let mut pids = cmd_args.pids;
+			if let Some(pattern) = &cmd_args.match_name {
+				let matches = match internal::find_pids_by_name(pattern) {
+					Ok(matches) => matches,
+					Err(e) => panic!("Error: While scanning processes: {e}"),
+				};
+				if matches.is_empty() {
+					println!("Warning: No processes found matching \"{pattern}\"");
+				}
+				pids.extend(matches.into_iter().filter(|pid| !cmd_args.exclude.contains(pid)));
 			}
-			for pid in cmd_args.pids {
+			for pid in pids {
+				if !cmd_args.verify {
+					cgroup.classify(pid);
+				} else if let Err(e) = cgroup.move_and_verify(pid) {
+					panic!("Error: {e}");
+				}
+				if cmd_args.with_descendants {
+					let moved = cgroup.classify_with_descendants(pid);
+					if !moved.is_empty() {
+						let moved = moved.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ");
+						println!("Notice: Also moved descendants of {pid}: {moved}");
+					}
+				}
+			}
+			for fd in cmd_args.pidfd {
+				let pid = match internal::pidfd_pid(fd) {
+					Ok(pid) => pid,
+					Err(e) => panic!("Error: While reading the process ID of pidfd {fd}: {e}"),
+				};
 				cgroup.classify(pid);
+				// If the process exited before it was classified, its ID may have been reused by another process
+				if let Err(e) = internal::pidfd_check_alive(fd) {
+					panic!("Error: Process {pid} of pidfd {fd} exited while being classified, so another process may have been moved instead: {e}");
+				}
 			}
 		}
 		Command::Control(
@@ -156,48 +826,553 @@ fn main() {
 			},
 		) => {
 			let mut inherit_cgroup = cgroup.clone();
-			inherit_cgroup.append(&inherit_cgroup_name);
+			inherit_cgroup.append(inherit_cgroup_name);
 			let controllers = inherit_cgroup.controllers();
-			cgroup.append(&cmd_args.cgroup);
-			if cmd_args.auto {
-				cgroup.create();
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
 			}
-			for controller in controllers {
-				cgroup.enable_controller(&*controller);
+			let outcomes = controllers
+				.into_iter()
+				.map(|controller| {
+					let outcome = cgroup.enable_controller(&controller);
+					(controller, outcome)
+				})
+				.collect::<Vec<_>>();
+			println!("Controllers in {cgroup}: {}", enable_summary(&outcomes));
+		}
+		Command::Control(cmd_args) if cmd_args.control.inherit_parent => {
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
 			}
+			let Some(parent) = cgroup.ancestors().into_iter().next() else {
+				panic!("Error: Control group {cgroup} has no parent to inherit from");
+			};
+			let outcomes = parent
+				.subtree_controllers()
+				.into_iter()
+				.map(|controller| {
+					let outcome = cgroup.enable_subtree_control(&controller);
+					(controller, outcome)
+				})
+				.collect::<Vec<_>>();
+			println!("Controllers in {cgroup}: {}", enable_summary(&outcomes));
 		}
 		Command::Control(cmd_args) if cmd_args.control.controllers.is_empty() => {
-			cgroup.append(&cmd_args.cgroup);
-			if cmd_args.auto {
-				cgroup.create();
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
+			}
+			let list = ControllerListOutput {
+				cgroup: cgroup.canonical_name(),
+				controllers: cgroup.controllers(),
+				subtree_control: cgroup.subtree_controllers(),
+			};
+			if cmd_args.format == Some(OutputFormat::Json) {
+				println!("{}", serde_json::to_string(&list).unwrap());
+			} else {
+				println!("Controllers enabled in {cgroup}: {}", list.controllers.join(" "));
+				println!(
+					"Controllers enabled for children of {cgroup}: {}",
+					list.subtree_control.join(" ")
+				);
 			}
-			let controllers = cgroup.controllers();
-			println!("Controllers enabled in {cgroup}: {controllers:?}");
 		}
 		Command::Control(cmd_args) => {
-			cgroup.append(&cmd_args.cgroup);
-			if cmd_args.auto {
-				cgroup.create();
-			}
-			for controller in cmd_args.control.controllers {
-				cgroup.enable_controller(&*controller.name);
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
 			}
+			let summary = cmd_args
+				.control
+				.controllers
+				.into_iter()
+				.map(|controller| {
+					let name = controller.name;
+					if controller.enable {
+						return enable_summary(&[(name.clone(), cgroup.enable_controller(&name))]);
+					}
+					let Some(parent) = cgroup.parent() else {
+						panic!("Error: Controllers of the root control group come from the kernel, so \"{name}\" cannot be disabled there");
+					};
+					match parent.disable_subtree_control(&[&name])[0].1 {
+						SetOutcome::Changed => format!("{name}: disabled"),
+						SetOutcome::Unchanged => format!("{name}: already disabled"),
+					}
+				})
+				.collect::<Vec<_>>()
+				.join(", ");
+			println!("Controllers in {cgroup}: {summary}");
 		}
 		Command::Restrict(cmd_args) => {
-			cgroup.append(&cmd_args.cgroup);
-			if cmd_args.auto {
-				cgroup.create();
+			cgroup.append(cmd_args.cgroup.name());
+			if cgroup.contains_current() {
+				println!("Warning: cg2util is running in control group {cgroup} or one of its descendants, so these restrictions also apply to the calling process");
 			}
-			for (key, value) in cmd_args.restrictions.iter() {
-				if cmd_args.auto {
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
+				for (key, _) in cmd_args.restrictions.iter() {
 					cgroup.enable_controller_for_restriction(key);
 				}
-				cgroup.set_restriction(key, value);
+			} else {
+				let keys: Vec<&str> = cmd_args.restrictions.iter().map(|(key, _)| key.as_str()).collect();
+				if let Some(controller) = cgroup.missing_controllers_for_restrictions(&keys).first() {
+					panic!("Error: controller '{controller}' is not enabled in {cgroup}; pass --auto to enable it");
+				}
+			}
+			cgroup.set_restrictions(&cmd_args.restrictions);
+		}
+		Command::WatchMemory(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let mut previous = cgroup.memory_events();
+			loop {
+				let current = cgroup.memory_current();
+				let events = cgroup.memory_events();
+				let throttled = if events.throttled_since(&previous) {
+					" throttled"
+				} else {
+					""
+				};
+				let current = if cmd_args.human {
+					internal::format_bytes(Limit::Value(current))
+				} else {
+					current.to_string()
+				};
+				println!(
+					"memory.current={current} high={} (+{}) oom={} oom_kill={}{throttled}",
+					events.high,
+					events.high_since(&previous),
+					events.oom,
+					events.oom_kill
+				);
+				previous = events;
+				thread::sleep(Duration::from_secs(cmd_args.interval));
+			}
+		}
+		Command::Tree(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let age = |cgroup: &CGroup| {
+				if !cmd_args.age {
+					return String::new();
+				}
+				match cgroup.created_at() {
+					Ok(t) => format!(" (age {})", internal::format_duration(t.elapsed().unwrap_or_default())),
+					Err(e) => format!(" (age unknown: {e})"),
+				}
+			};
+			// Frozen groups in cyan, groups with processes in green
+			let paint = |cgroup: &CGroup, name: &str| {
+				if !color {
+					name.to_string()
+				} else if cgroup.is_frozen() {
+					internal::paint(name, internal::Color::Cyan, true)
+				} else if cgroup.is_populated() {
+					internal::paint(name, internal::Color::Green, true)
+				} else {
+					name.to_string()
+				}
+			};
+			let base_depth = cgroup.as_cgroup_path().components().count();
+			println!("{}{}", paint(&cgroup, &cgroup.canonical_name()), age(&cgroup));
+			for descendant in cgroup.descendants(cmd_args.jobs) {
+				let path = descendant.as_cgroup_path();
+				let indent = "  ".repeat(path.components().count() - base_depth);
+				let name = path.file_name().unwrap_or_default().to_string_lossy();
+				println!("{indent}{}{}", paint(&descendant, &name), age(&descendant));
+			}
+		}
+		Command::Gc(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			// Deleting a child updates the modification time of its parent, which is what created_at reads, so
+			// read every age before deleting anything
+			let candidates = cgroup
+				.descendants(1)
+				.into_iter()
+				.map(|candidate| match candidate.created_at().map(|t| t.elapsed().unwrap_or_default()) {
+					Ok(age) => (candidate, age),
+					Err(e) => panic!("Error: While reading the age of control group {candidate}: {e}"),
+				})
+				.collect::<Vec<_>>();
+			// Children come after their parents, so visit in reverse to collect leaves before their parents
+			let mut deleted = HashSet::new();
+			for (candidate, age) in candidates.into_iter().rev() {
+				let has_children = candidate
+					.children()
+					.iter()
+					.any(|child| !deleted.contains(child.as_cgroup_path()));
+				if has_children || candidate.is_populated() || candidate.contains_current() {
+					continue;
+				}
+				if age < cmd_args.older_than {
+					continue;
+				}
+				let age = internal::format_duration(age);
+				if candidate.is_dry_run() {
+					println!("Would delete control group {candidate} (age {age})");
+				} else {
+					match candidate.remove() {
+						Ok(()) => println!("Notice: Deleted control group {candidate} (age {age})"),
+						Err(e) => panic!("Error: While deleting control group {candidate}: {e}"),
+					}
+				}
+				deleted.insert(candidate.as_cgroup_path().to_path_buf());
+			}
+		}
+		Command::Reclaim(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let reclaimed = cgroup.memory_reclaim(cmd_args.bytes);
+			if !cgroup.is_dry_run() {
+				println!(
+					"Reclaimed about {reclaimed} of {} bytes requested from control group {cgroup}",
+					cmd_args.bytes
+				);
+			}
+		}
+		Command::NumaStat(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let stat = cgroup.memory_numa_stat();
+			if cmd_args.json {
+				println!("{}", serde_json::to_string(&stat).unwrap());
+			} else {
+				for (node, categories) in stat.nodes {
+					let categories = categories
+						.iter()
+						.map(|(category, bytes)| format!("{category}={bytes}"))
+						.collect::<Vec<_>>()
+						.join(" ");
+					println!("N{node} {categories}");
+				}
+			}
+		}
+		Command::Shares(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			for (child, share) in cgroup.weight_tree() {
+				match share {
+					Some((weight, percent)) => println!("{child}: {percent:.1}% (cpu.weight={weight})"),
+					None => {
+						println!("{child}: no cpu.weight; the cpu controller is not enabled for children of {cgroup}")
+					}
+				}
+			}
+		}
+		Command::Resolve(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let cgroup = CGroup::from_cgroup_path(cgroup.canonical_name());
+			println!("{cgroup}");
+			println!("{}", cgroup.cgroupfs_path().display());
+		}
+		Command::CheckProfile(cmd_args) => {
+			let (path, contents) = match Profile::read(&cmd_args.profile) {
+				Ok(profile) => profile,
+				Err(e) => panic!("Error: While loading profile {}: {e}", cmd_args.profile),
+			};
+			let available = CGroup::root().controllers();
+			let problems = Profile::problems(&contents, &available);
+			if !problems.is_empty() {
+				for problem in problems {
+					println!("Error: {}: {problem}", path.display());
+				}
+				process::exit(1);
+			}
+			println!("Profile {} is valid", path.display());
+		}
+		Command::Chain(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let controller = &cmd_args.controller;
+			let chain = cgroup.delegation_chain(controller);
+			let mut broken = false;
+			for (i, (level, enabled)) in chain.iter().enumerate() {
+				if *enabled {
+					println!("{level}: {controller} enabled for children");
+					continue;
+				}
+				if broken {
+					println!("{level}: {controller} not enabled for children");
+					continue;
+				}
+				broken = true;
+				let affected = match chain.get(i + 1) {
+					Some((child, _)) => format!("{child} and below"),
+					None => format!("children of {level}"),
+				};
+				println!("{level}: {controller} NOT enabled for children <- delegation breaks here, so {affected} cannot use {controller}");
+			}
+		}
+		Command::Delete(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			internal::refuse_if_contains_current(&cgroup, "delete", cmd_args.force);
+			let mut targets = Vec::new();
+			if cmd_args.recursive {
+				// Children come after their parents, so visit in reverse to delete leaves before their parents
+				targets.extend(cgroup.descendants(1).into_iter().rev());
+			}
+			targets.push(cgroup);
+			for target in targets {
+				match target.remove() {
+					Ok(()) if target.is_dry_run() => (),
+					Ok(()) => println!("Notice: Deleted control group {target}"),
+					Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+						panic!("Error: Control group {target} is not empty")
+					}
+					Err(e) => panic!("Error: While deleting control group {target}: {e}"),
+				}
+			}
+		}
+		Command::Freeze(cmd_args) => set_frozen(cgroup, &cmd_args, true),
+		Command::Thaw(cmd_args) => set_frozen(cgroup, &cmd_args, false),
+		Command::Kill(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			internal::refuse_if_contains_current(&cgroup, "kill", cmd_args.force);
+			if let Err(e) = cgroup.kill_all() {
+				panic!("Error: While killing the processes of control group {cgroup}: {e}");
+			}
+			if !cgroup.is_dry_run() {
+				println!("Notice: Killed the processes of control group {cgroup}");
+			}
+		}
+		Command::Ps(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let pids = if cmd_args.recursive {
+				cgroup.processes_recursive()
+			} else {
+				cgroup.processes()
+			};
+			let pids = match pids {
+				Ok(pids) => pids,
+				Err(e) => panic!("Error: While listing the processes of control group {cgroup}: {e}"),
+			};
+			for pid in pids {
+				println!("{pid}");
+			}
+		}
+		Command::Stat(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let stat = match cgroup.stat() {
+				Ok(stat) => stat,
+				Err(e) => panic!("Error: While reading cgroup.stat of control group {cgroup}: {e}"),
+			};
+			println!("descendants: {}", stat.nr_descendants);
+			println!("dying descendants: {}", stat.nr_dying_descendants);
+		}
+		Command::Type(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.threaded {
+				if let Err(e) = cgroup.set_threaded() {
+					panic!("Error: While making control group {cgroup} threaded: {e}");
+				}
+			} else {
+				println!("{}", cgroup.cgroup_type());
+			}
+		}
+		Command::Io(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			// io.max resets the limits that are written as "max", so start from the current ones
+			let mut limits = cgroup.io_max().remove(&cmd_args.device.to_string()).unwrap_or_default();
+			limits.rbps = cmd_args.rbps.unwrap_or(limits.rbps);
+			limits.wbps = cmd_args.wbps.unwrap_or(limits.wbps);
+			limits.riops = cmd_args.riops.unwrap_or(limits.riops);
+			limits.wiops = cmd_args.wiops.unwrap_or(limits.wiops);
+			cgroup.set_io_limit(cmd_args.device, limits);
+		}
+		Command::Memory(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let restrictions: Vec<(String, String)> = [
+				("memory.high", cmd_args.high),
+				("memory.max", cmd_args.max),
+				("memory.swap.max", cmd_args.swap_max),
+			]
+			.into_iter()
+			.filter_map(|(key, limit)| Some((key.to_string(), limit?.to_string())))
+			.collect();
+			cgroup.set_restrictions(&restrictions);
+		}
+		Command::Cpu(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let mut restrictions = Vec::new();
+			if let Some(percent) = cmd_args.percent {
+				restrictions.push(("cpu.max".to_string(), cpu_max_line(percent, cmd_args.period)));
+			}
+			if let Some(weight) = cmd_args.weight {
+				restrictions.push(("cpu.weight".to_string(), weight.to_string()));
+			}
+			cgroup.set_restrictions(&restrictions);
+		}
+		Command::Exists(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			match cgroup.exists() {
+				Ok(true) => {}
+				Ok(false) => process::exit(1),
+				Err(e) => {
+					println!("Error: While checking whether control group {cgroup} exists: {e}");
+					process::exit(2);
+				}
+			}
+		}
+		Command::Get(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			println!("{}", cgroup.read_restriction(&cmd_args.key));
+		}
+		Command::Status(cmd_args) => {
+			// Every name is appended to the same base, so /proc/self/cgroup is only read once
+			let statuses = cmd_args
+				.cgroups
+				.names()
+				.map(|name| {
+					let mut target = cgroup.clone();
+					target.append(name);
+					StatusOutput::read(&target)
+				})
+				.collect::<Vec<_>>();
+			if cmd_args.json {
+				println!("{}", serde_json::to_string(&statuses).unwrap());
+			} else {
+				let blocks = statuses.iter().map(|status| status.to_text(color)).collect::<Vec<_>>();
+				print!("{}", blocks.join("\n"));
+			}
+		}
+		Command::PrepareFor(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			if cmd_args.auto || config_auto {
+				create_cgroup(&cgroup);
+			}
+			let outcomes = cgroup.enable_controllers_for_restrictions(&cmd_args.keys);
+			println!("Controllers in {cgroup}: {}", enable_summary(&outcomes));
+		}
+		Command::Boost(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			let (key, value) = &cmd_args.restriction;
+			let previous = match cgroup.get_restriction(key) {
+				Ok(previous) => previous,
+				Err(e) => panic!("Error: While reading {key} of control group {cgroup}: {e}"),
+			};
+			if previous.parse::<Limit>().is_err() {
+				panic!("Error: Cannot boost {key} of control group {cgroup} because its value \"{previous}\" is not a single number or \"max\"");
+			}
+			// Signals stay pending from here on, so that the previous value is restored if this process is stopped
+			internal::block_termination_signals();
+			cgroup.set_restriction(key, value);
+			if cgroup.is_dry_run() {
+				// Nothing was changed, so print the restoring write without waiting for it
+				cgroup.set_restriction(key, &previous);
+			} else {
+				println!(
+					"Notice: Restoring {key}=\"{previous}\" in {}",
+					internal::format_duration(cmd_args.duration)
+				);
+				let signal = internal::wait_for_termination(cmd_args.duration);
+				cgroup.set_restriction(key, &previous);
+				if let Some(signal) = signal {
+					process::exit(128 + signal);
+				}
+			}
+		}
+		Command::Reset(cmd_args) => {
+			cgroup.append(cmd_args.cgroup.name());
+			internal::refuse_if_contains_current(&cgroup, "reset", cmd_args.force);
+			let mut targets = Vec::new();
+			if cmd_args.recursive {
+				// Children come after their parents, so reverse to reset from the leaves upward
+				targets.extend(cgroup.descendants(1).into_iter().rev());
+			}
+			targets.push(cgroup);
+			for target in targets {
+				if !cmd_args.force && target.is_populated() {
+					println!("Notice: Skipping control group {target} because it has processes. Pass --force to reset it anyway.");
+					continue;
+				}
+				let skipped = target.reset_restrictions();
+				if !skipped.is_empty() {
+					println!(
+						"Notice: No known default for {} in control group {target}; left unchanged",
+						skipped.join(" ")
+					);
+				}
 			}
 		}
 	}
 }
 
+/// Parses a flag value from the configuration, exiting with an error if it is not valid for the flag.
+fn config_value<T: ValueEnum>(value: &Option<String>, key: &str) -> Option<T> {
+	let value = value.as_ref()?;
+	match T::from_str(value, true) {
+		Ok(value) => Some(value),
+		Err(e) => {
+			println!("Error: Invalid value for {key} in the configuration: {e}");
+			process::exit(1);
+		}
+	}
+}
+
+/// Creates the control group if it doesn't exist yet, exiting with an error if that fails.
+fn create_cgroup(cgroup: &CGroup) {
+	match cgroup.create() {
+		Ok(true) => report_created(cgroup),
+		Ok(false) => (),
+		Err(e) => internal::exit_with_error(&format!("Error: While creating control group {cgroup}: {e}")),
+	}
+}
+
+/// Prints that a control group was created, or the directory that a dry run would have created.
+fn report_created(cgroup: &CGroup) {
+	if cgroup.is_dry_run() {
+		println!("Would create directory {}", cgroup.cgroupfs_path().display());
+	} else {
+		println!("Notice: Created control group {cgroup}");
+	}
+}
+
+/// Runs `cg2util freeze` or `cg2util thaw`.
+fn set_frozen(mut cgroup: CGroup, cmd_args: &FreezeCommand, frozen: bool) {
+	cgroup.append(cmd_args.cgroup.name());
+	if frozen {
+		internal::refuse_if_contains_current(&cgroup, "freeze", cmd_args.force);
+	}
+	let state = if frozen { "frozen" } else { "thawed" };
+	if let Err(e) = cgroup.set_frozen(frozen) {
+		panic!("Error: While writing cgroup.freeze of control group {cgroup}: {e}");
+	}
+	// A dry run changes nothing, so there is no new state to wait for or report
+	if cgroup.is_dry_run() {
+		return;
+	}
+	if cmd_args.wait && !cgroup.wait_until_frozen(frozen, cmd_args.timeout) {
+		panic!(
+			"Error: Control group {cgroup} is not {state} after {}",
+			internal::format_duration(cmd_args.timeout)
+		);
+	}
+	println!("Notice: Control group {cgroup} {state}");
+}
+
+/// Summarizes which controllers were newly enabled, such as "cpu: already enabled, memory: enabled".
+fn enable_summary(outcomes: &[(String, SetOutcome)]) -> String {
+	outcomes
+		.iter()
+		.map(|(controller, outcome)| match outcome {
+			SetOutcome::Changed => format!("{controller}: enabled"),
+			SetOutcome::Unchanged => format!("{controller}: already enabled"),
+		})
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+/// Formats a panic payload as a JSON error object. Failures that are not a [`CGroupError`] are reported with the
+/// "other" error type and their prose message.
+fn error_json(payload: &(dyn Any + Send)) -> String {
+	if let Some(e) = payload.downcast_ref::<CGroupError>() {
+		return serde_json::to_string(e).unwrap();
+	}
+	let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+		(Some(message), _) => message,
+		(_, Some(message)) => message.as_str(),
+		_ => "unknown error",
+	};
+	let message = message.strip_prefix("Error: ").unwrap_or(message);
+	serde_json::json!({ "error": "other", "message": message }).to_string()
+}
+
 #[test]
 fn test_cli_create() {
 	fn cli(input: &str) -> Result<Cli, String> {
@@ -211,6 +1386,10 @@ fn test_cli_create() {
 	insta::assert_debug_snapshot!(cli("cg2util --auto create grp"));
 	insta::assert_debug_snapshot!(cli("cg2util create --auto grp"));
 	insta::assert_debug_snapshot!(cli("cg2util create grp --auto"));
+	insta::assert_debug_snapshot!(cli("cg2util create grp --template batch"));
+	insta::assert_debug_snapshot!(cli("cg2util create grp --template batch --force"));
+	insta::assert_debug_snapshot!(cli("cg2util create grp --force"));
+	insta::assert_debug_snapshot!(cli("cg2util create a/b/c --enable cpu,memory"));
 }
 
 #[test]
@@ -229,6 +1408,9 @@ fn test_cli_classify() {
 	insta::assert_debug_snapshot!(cli("cg2util classify --auto grp 123"));
 	insta::assert_debug_snapshot!(cli("cg2util classify grp --auto 123"));
 	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --auto"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp --match-name stress"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --match-name stress --exclude 456,789"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --exclude 456"));
 }
 
 #[test]
@@ -255,6 +1437,15 @@ fn test_cli_control() {
 	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit=igrp"));
 	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit igrp +cpu"));
 	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit +cpu"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --json"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --json +cpu"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit-parent"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit-parent +cpu"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --inherit-parent --json"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --format json"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --format json --json"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp --format yaml"));
+	insta::assert_debug_snapshot!(cli("cg2util control grp -"));
 }
 
 #[test]
@@ -275,4 +1466,425 @@ fn test_cli_restrict() {
 	insta::assert_debug_snapshot!(cli("cg2util restrict --auto grp cpu.max=90000"));
 	insta::assert_debug_snapshot!(cli("cg2util restrict grp --auto cpu.max=90000"));
 	insta::assert_debug_snapshot!(cli("cg2util restrict grp cpu.max=90000 --auto"));
+	insta::assert_debug_snapshot!(cli(
+		"cg2util restrict grp 'io.max=8:16 rbps=2097152 wbps=max' cpuset.cpus=0-3,8"
+	));
+}
+
+#[test]
+fn test_cli_watch_memory() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory"));
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory grp"));
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory grp --interval 5"));
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory grp --interval x"));
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory grp --human"));
+	insta::assert_debug_snapshot!(cli("cg2util watch-memory grp -H"));
+}
+
+#[test]
+fn test_cli_tree() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util tree"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --jobs 8"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp -j 8"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --age"));
+	insta::assert_debug_snapshot!(cli("cg2util --color always tree grp"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --color=never"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --color=sometimes"));
+}
+
+#[test]
+fn test_cli_gc() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util gc grp"));
+	insta::assert_debug_snapshot!(cli("cg2util gc grp --older-than 1h"));
+	insta::assert_debug_snapshot!(cli("cg2util gc grp --older-than 1h --dry-run"));
+	insta::assert_debug_snapshot!(cli("cg2util gc grp --older-than 1"));
+}
+
+#[test]
+fn test_cli_error_format() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util --error-format json tree grp"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --error-format=json"));
+	insta::assert_debug_snapshot!(cli("cg2util --error-format xml tree grp"));
+}
+
+#[test]
+fn test_error_format() {
+	let errors = [
+		CGroupError::NotFound {
+			cgroup: "/a/b".to_string(),
+		},
+		CGroupError::PermissionDenied {
+			cgroup: "/a/b".to_string(),
+			file: "cgroup.procs".to_string(),
+		},
+		CGroupError::Unavailable {
+			cgroup: "/a/b".to_string(),
+			file: "memory.max".to_string(),
+		},
+		CGroupError::Io {
+			cgroup: "/a/b".to_string(),
+			file: "cpu.max".to_string(),
+			message: "Invalid argument (os error 22)".to_string(),
+		},
+	];
+	for e in errors {
+		insta::assert_snapshot!(format!("Error: {e}\n{}", error_json(&e)));
+	}
+	insta::assert_snapshot!(error_json(&"Error: Controller \"bogus\" is not available"));
+	insta::assert_snapshot!(error_json(&String::from("Error: While scanning processes: oops")));
+	let e = CGroupError::ControllerNotEnabled {
+		cgroup: "/a/b".to_string(),
+		file: "memory.max".to_string(),
+		controller: "memory".to_string(),
+		parent: "/a".to_string(),
+	};
+	insta::assert_snapshot!(format!("Error: {e}\n{}", error_json(&e)));
+}
+
+#[test]
+fn test_enable_summary() {
+	insta::assert_snapshot!(enable_summary(&[
+		("cpu".to_string(), SetOutcome::Unchanged),
+		("memory".to_string(), SetOutcome::Changed),
+	]));
+	insta::assert_snapshot!(enable_summary(&[
+		("cpu".to_string(), SetOutcome::Changed),
+		("io".to_string(), SetOutcome::Changed),
+		("pids".to_string(), SetOutcome::Changed),
+	]));
+	insta::assert_snapshot!(enable_summary(&[]));
+}
+
+#[test]
+fn test_cli_reset() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util reset"));
+	insta::assert_debug_snapshot!(cli("cg2util reset grp"));
+	insta::assert_debug_snapshot!(cli("cg2util reset grp --recursive"));
+	insta::assert_debug_snapshot!(cli("cg2util reset grp --recursive --force"));
+}
+
+#[test]
+fn test_cli_reclaim() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util reclaim grp"));
+	insta::assert_debug_snapshot!(cli("cg2util reclaim grp 1048576"));
+	insta::assert_debug_snapshot!(cli("cg2util reclaim grp 1M"));
+}
+
+#[test]
+fn test_cli_shares() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util shares"));
+	insta::assert_debug_snapshot!(cli("cg2util shares grp"));
+}
+
+#[test]
+fn test_cli_resolve() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util resolve ../grp"));
+	insta::assert_debug_snapshot!(cli("cg2util resolve"));
+}
+
+#[test]
+fn test_cli_check_profile() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util check-profile batch"));
+	insta::assert_debug_snapshot!(cli("cg2util check-profile ./batch.profile extra"));
+}
+
+#[test]
+fn test_cli_chain() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util chain grp"));
+	insta::assert_debug_snapshot!(cli("cg2util chain grp cpu"));
+}
+
+#[test]
+fn test_cli_delete() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util delete"));
+	insta::assert_debug_snapshot!(cli("cg2util delete grp"));
+	insta::assert_debug_snapshot!(cli("cg2util delete --recursive grp"));
+	insta::assert_debug_snapshot!(cli("cg2util delete --force grp"));
+}
+
+#[test]
+fn test_cli_freeze() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util freeze grp"));
+	insta::assert_debug_snapshot!(cli("cg2util freeze grp --wait"));
+	insta::assert_debug_snapshot!(cli("cg2util thaw grp --wait --timeout 30s"));
+	insta::assert_debug_snapshot!(cli("cg2util thaw grp --timeout 30s"));
+}
+
+#[test]
+fn test_cli_kill() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util kill grp"));
+	insta::assert_debug_snapshot!(cli("cg2util kill --force grp"));
+}
+
+#[test]
+fn test_cli_ps() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util ps grp"));
+	insta::assert_debug_snapshot!(cli("cg2util ps --recursive grp"));
+}
+
+#[test]
+fn test_cli_stat() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util stat"));
+	insta::assert_debug_snapshot!(cli("cg2util stat grp"));
+}
+
+#[test]
+fn test_cli_type() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util type grp"));
+	insta::assert_debug_snapshot!(cli("cg2util type grp --threaded"));
+}
+
+#[test]
+fn test_cli_io() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util io grp --device 8:0 --rbps 1M --wbps 512k --riops max"));
+	insta::assert_debug_snapshot!(cli("cg2util io grp --device 8:0"));
+	insta::assert_debug_snapshot!(cli("cg2util io grp --device /dev/null --rbps 1M"));
+	insta::assert_debug_snapshot!(cli("cg2util io grp --device 8:0 --wbps 1.5M"));
+}
+
+#[test]
+fn test_cli_memory() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util memory grp --high 512M --max 1Gi --swap-max 0"));
+	insta::assert_debug_snapshot!(cli("cg2util memory grp --max max"));
+	insta::assert_debug_snapshot!(cli("cg2util memory grp"));
+	insta::assert_debug_snapshot!(cli("cg2util memory grp --high 1GB"));
+}
+
+#[test]
+fn test_cli_cpu() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util cpu grp --percent 150 --weight 200"));
+	insta::assert_debug_snapshot!(cli("cg2util cpu grp --percent 50 --period 10ms"));
+	insta::assert_debug_snapshot!(cli("cg2util cpu grp --weight 0"));
+	insta::assert_debug_snapshot!(cli("cg2util cpu grp --percent 50 --period 2s"));
+	insta::assert_debug_snapshot!(cli("cg2util cpu grp --weight 100 --period 10ms"));
+}
+
+#[test]
+fn test_cli_exists() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util exists /grp"));
+}
+
+#[test]
+fn test_cpu_max_line() {
+	assert_eq!(cpu_max_line(150.0, Duration::from_millis(100)), "150000 100000");
+	assert_eq!(cpu_max_line(100.0, Duration::from_secs(1)), "1000000 1000000");
+	assert_eq!(cpu_max_line(12.5, Duration::from_millis(10)), "1250 10000");
+	// Quotas are rounded to the nearest microsecond
+	assert_eq!(cpu_max_line(33.3333, Duration::from_millis(100)), "33333 100000");
+	assert_eq!(cpu_max_line(66.6667, Duration::from_millis(100)), "66667 100000");
+	assert_eq!(cpu_max_line(0.0015, Duration::from_millis(100)), "2 100000");
+	assert_eq!(cpu_max_line(400.0, Duration::from_micros(2500)), "10000 2500");
+}
+
+#[test]
+fn test_cli_get() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util get grp"));
+	insta::assert_debug_snapshot!(cli("cg2util get grp cpu.weight"));
+}
+
+#[test]
+fn test_cli_numa_stat() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util numa-stat"));
+	insta::assert_debug_snapshot!(cli("cg2util numa-stat grp"));
+	insta::assert_debug_snapshot!(cli("cg2util numa-stat grp --json"));
+}
+
+#[test]
+fn test_cli_status() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util status"));
+	insta::assert_debug_snapshot!(cli("cg2util status grp"));
+	insta::assert_debug_snapshot!(cli("cg2util status grp1 /grp2 ../grp3 --json"));
+}
+
+#[test]
+fn test_cli_classify_pidfd() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util classify grp --pidfd 3"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --pidfd 3,4"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --verify"));
+	insta::assert_debug_snapshot!(cli("cg2util classify grp 123 --with-descendants"));
+}
+
+#[test]
+fn test_status_text() {
+	let status = StatusOutput {
+		cgroup: "/a".to_string(),
+		populated: true,
+		frozen: false,
+		nr_descendants: 2,
+		controllers: vec!["cpu".to_string(), "memory".to_string()],
+		subtree_control: vec!["cpu".to_string()],
+		memory_current: Some(4096),
+		memory_max: Some(Limit::Value(4096)),
+		summary: "Control group /a can use the cpu and memory controllers.".to_string(),
+	};
+	assert_eq!(
+		status.to_text(false),
+		"Control group /a:\n  populated: true\n  frozen: false\n  descendants: 2\n  controllers: cpu memory\n  subtree_control: cpu\n  memory.current: 4096\n  summary: Control group /a can use the cpu and memory controllers.\n"
+	);
+	let colored = status.to_text(true);
+	assert!(colored.contains("Control group \x1b[32m/a\x1b[0m:"));
+	assert!(colored.contains("controllers: \x1b[33mcpu\x1b[0m \x1b[33mmemory\x1b[0m"));
+	assert!(colored.contains("memory.current: \x1b[31m4096\x1b[0m"));
+	assert!(colored.contains("frozen: false\n"));
+}
+
+#[test]
+fn test_status_root() {
+	let root = tempfile::tempdir().unwrap();
+	for (name, contents) in [
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", "cpu memory\n"),
+		("cgroup.stat", "nr_descendants 3\nnr_dying_descendants 0\n"),
+		("cgroup.procs", "1\n"),
+	] {
+		std::fs::write(root.path().join(name), contents).unwrap();
+	}
+	let status = StatusOutput::read(&CGroup::with_root(root.path(), "/"));
+	assert!(status.populated);
+	assert!(!status.frozen);
+	assert_eq!(status.nr_descendants, 3);
+	assert_eq!(status.memory_current, None);
+	assert!(!status.to_text(false).contains("memory.current"));
+}
+
+#[test]
+fn test_cli_prepare_for() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util prepare-for grp"));
+	insta::assert_debug_snapshot!(cli("cg2util prepare-for grp cpu.max memory.max io.weight"));
+	insta::assert_debug_snapshot!(cli("cg2util prepare-for --auto grp cpu.max"));
+}
+
+#[test]
+fn test_cli_audit_log() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli(
+		"cg2util --audit-log /var/log/cg2tools.jsonl restrict grp memory.max=1G"
+	));
+	insta::assert_debug_snapshot!(cli("cg2util restrict grp memory.max=1G --audit-log audit.jsonl"));
+}
+
+#[test]
+fn test_cli_io_timeout() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util --io-timeout 5s restrict grp memory.max=1G"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --io-timeout 500ms"));
+	insta::assert_debug_snapshot!(cli("cg2util --io-timeout 5 tree grp"));
+}
+
+#[test]
+fn test_cli_dry_run() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util --dry-run restrict grp cpu.weight=100"));
+	insta::assert_debug_snapshot!(cli("cg2util control --dry-run grp +cpu"));
+}
+
+#[test]
+fn test_cli_cgroup_flag() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		let args = shlex::split(input).unwrap().into_iter().map(std::ffi::OsString::from);
+		internal::parse_args::<Cli>(args).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util classify --cgroup grp 123"));
+	insta::assert_debug_snapshot!(cli("cg2util --color never restrict cpu.weight=150 --cgroup=/custom"));
+	insta::assert_debug_snapshot!(cli("cg2util status --cgroup a --cgroup b"));
+	insta::assert_debug_snapshot!(cli("cg2util tree grp --cgroup other"));
+	insta::assert_debug_snapshot!(cli("cg2util status a --cgroup b"));
+	insta::assert_debug_snapshot!(cli("cg2util classify a 123 --cgroup b"));
+	insta::assert_debug_snapshot!(cli("cg2util get --cgroup grp cpu.weight"));
+	insta::assert_debug_snapshot!(cli("cg2util control --cgroup grp -cpu"));
+	insta::assert_debug_snapshot!(cli("cg2util restrict --cgroup grp"));
+	insta::assert_debug_snapshot!(cli("cg2util classify --cgroup grp"));
+	assert!(cli("cg2util tree --help").unwrap_err().contains("--cgroup <CGROUP>"));
+}
+
+#[test]
+fn test_cli_boost() {
+	fn cli(input: &str) -> Result<Cli, String> {
+		Cli::try_parse_from(shlex::split(input).unwrap()).map_err(|e| format!("{e}"))
+	}
+	insta::assert_debug_snapshot!(cli("cg2util boost grp memory.high=4G"));
+	insta::assert_debug_snapshot!(cli("cg2util boost grp memory.high=4G --for 30s"));
+	insta::assert_debug_snapshot!(cli("cg2util boost grp memory.high --for 30s"));
 }
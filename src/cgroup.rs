@@ -12,21 +12,77 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::audit;
+use crate::events;
+use crate::internal;
+use crate::pressure;
+use crate::CGroupError;
+use crate::CGroupSnapshot;
+use crate::CGroupStat;
+use crate::CpusetPartition;
+use crate::DeviceNumber;
+use crate::IoLimits;
+use crate::Limit;
+use crate::MemoryEvents;
+use crate::MemoryLimits;
+use crate::MemoryNumaStat;
+use crate::MemoryStat;
+use crate::MemoryStats;
+use crate::Percentage;
+use crate::Profile;
+use crate::RestrictionValue;
+use crate::TimeoutError;
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::io::Seek;
 use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+/// Maximum length in bytes of one component of a path, not including a terminating null byte.
+const NAME_MAX: usize = 255;
+
+/// Maximum length in bytes of a path, including a terminating null byte.
+const PATH_MAX: usize = 4096;
 
 /// A control group that may or may not exist on disk.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct CGroup(PathBuf);
+pub struct CGroup {
+	/// Path of the control group relative to the cgroup file system, starting with "/".
+	path: PathBuf,
+	/// Mount point of the cgroup file system.
+	root: PathBuf,
+	/// Whether changes are printed instead of made, as set with [`CGroup::with_dry_run`].
+	dry_run: bool,
+}
 
 impl CGroup {
+	/// Maximum number of threads used by [`CGroup::descendants`].
+	pub const MAX_JOBS: usize = 64;
+
+	/// The longest time [`CGroup::wait_until_empty`] and [`CGroup::wait_until_frozen`] wait for a notification before
+	/// checking again.
+	pub const EMPTY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+	/// Maximum number of times [`CGroup::classify_with_descendants`] walks the process tree.
+	pub const MAX_TREE_WALKS: usize = 3;
+
 	/// Reads the control group of the current process and returns it.
 	pub fn current() -> Self {
 		Self::from_proc_pid_cgroup(process::id())
@@ -34,27 +90,142 @@ impl CGroup {
 
 	/// Reads the control group of the given process ID and returns it.
 	pub fn from_proc_pid_cgroup(pid: u32) -> Self {
-		let mut path = PathBuf::from("/proc");
+		let mut path = internal::proc_root();
 		path.push(pid.to_string());
 		path.push("cgroup");
 		let file_contents = fs::read_to_string(&path).unwrap();
+		Self::from_proc_cgroup_contents(&file_contents)
+	}
+
+	/// Reads the control group of the process referred to by a pidfd and returns it.
+	///
+	/// Unlike [`CGroup::from_proc_pid_cgroup`], this cannot return the control group of an unrelated process that
+	/// reused the process ID: after reading `/proc/<pid>/cgroup`, the pidfd is checked to confirm that the process had
+	/// not exited in the meantime. Requires Linux 5.5 or newer; older kernels return an [`io::ErrorKind::Unsupported`]
+	/// error, in which case the caller may fall back to [`CGroup::from_proc_pid_cgroup`].
+	pub fn from_pidfd(fd: RawFd) -> io::Result<Self> {
+		let pid = internal::pidfd_pid(fd)?;
+		let file_contents = fs::read_to_string(internal::proc_root().join(pid.to_string()).join("cgroup"))?;
+		internal::pidfd_check_alive(fd)?;
+		Ok(Self::from_proc_cgroup_contents(&file_contents))
+	}
+
+	fn from_proc_cgroup_contents(file_contents: &str) -> Self {
 		let Some(s) = file_contents.trim().strip_prefix("0::") else {
 			panic!("Error: Unexpected format in cgroup file. Are you using cgroups v1?\n\n{file_contents}");
 		};
-		Self(PathBuf::from(s))
+		Self::from_cgroup_path(s)
 	}
 
-	/// Creates a [`CGroup`] from a path relative to the cgroup file system.
+	/// Creates a [`CGroup`] from a path relative to the cgroup file system, which is mounted at
+	/// [`internal::cgroupfs_root`].
 	pub fn from_cgroup_path(path: impl AsRef<Path>) -> Self {
-		Self(PathBuf::from(path.as_ref()))
+		Self::with_root(internal::cgroupfs_root(), path)
+	}
+
+	/// Returns the root control group, "/", which holds the whole hierarchy.
+	///
+	/// The root differs from other groups: it has no parent, so its controllers come from the kernel rather than
+	/// being enabled, and it has no resource limits, so files such as "memory.max" do not exist in it.
+	pub fn root() -> Self {
+		Self::from_cgroup_path("/")
+	}
+
+	/// Finds where the cgroup2 file system is mounted from `/proc/self/mountinfo`.
+	///
+	/// On systems with a hybrid hierarchy, this is usually "/sys/fs/cgroup/unified" rather than "/sys/fs/cgroup". If
+	/// there is no cgroup2 mount, "/sys/fs/cgroup" is returned. If there are several, the first one is returned.
+	pub fn detect_mount_point() -> io::Result<PathBuf> {
+		let contents = fs::read_to_string(internal::proc_root().join("self/mountinfo"))?;
+		Ok(cgroup2_mount_point(&contents).unwrap_or_else(|| PathBuf::from("/sys/fs/cgroup")))
+	}
+
+	/// Returns true if this is the root control group, after resolving "." and ".." components.
+	pub fn is_root(&self) -> bool {
+		self.canonical_name() == "/"
+	}
+
+	/// Creates a [`CGroup`] for a systemd unit, such as "myservice.service".
+	///
+	/// The control group is looked up with `systemctl show`. If systemd cannot be queried or does not know the unit,
+	/// the conventional path is used instead: services and other units under `/system.slice`, and slices nested by
+	/// the dashes in their names (for example, `/a.slice/a-b.slice`).
+	pub fn from_systemd_unit(unit: &str) -> Result<Self, String> {
+		validate_unit_name(unit)?;
+		let path = systemctl_control_group(unit).unwrap_or_else(|| conventional_unit_path(unit));
+		Ok(Self::from_cgroup_path(path))
+	}
+
+	/// Creates a [`CGroup`] in a cgroup file system mounted at `root`, such as "/cgroup2" in a container, instead of the
+	/// default mount point.
+	///
+	/// Groups derived from this one, such as with [`CGroup::append`] or [`CGroup::parent`], keep the same root.
+	pub fn with_root(root: impl AsRef<Path>, path: impl AsRef<Path>) -> Self {
+		Self {
+			path: PathBuf::from(path.as_ref()),
+			root: PathBuf::from(root.as_ref()),
+			dry_run: false,
+		}
+	}
+
+	/// Makes operations that change this [`CGroup`] print the change instead of making it. Reads are still made, so
+	/// that the printed changes reflect the current state.
+	///
+	/// Groups derived from this one keep the setting, like the root.
+	pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+		self.dry_run = dry_run;
+		self
+	}
+
+	/// Returns whether this [`CGroup`] is in a dry run, as set with [`CGroup::with_dry_run`].
+	pub fn is_dry_run(&self) -> bool {
+		self.dry_run
 	}
 
 	/// Returns this [`CGroup`] as a path relative to the cgroup file system.
 	pub fn as_cgroup_path(&self) -> &Path {
-		&self.0
+		&self.path
+	}
+
+	/// Returns a normalized name of this [`CGroup`] that is suitable for display and machine-readable output.
+	///
+	/// Unlike [`CGroup::as_cgroup_path`], which returns the literal path including any "." and ".." components
+	/// from relative names, this resolves those components. Leading ".." components, which appear when the process
+	/// that reported the path is in a cgroup namespace that doesn't include this group, are dropped so that the
+	/// name is always relative to the visible root of the hierarchy.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::CGroup;
+	///
+	/// let mut cgroup = CGroup::from_cgroup_path("/service/main");
+	/// cgroup.append("../subproc/./tier1/");
+	/// assert_eq!(cgroup.as_cgroup_path().to_str(), Some("/service/main/../subproc/./tier1/"));
+	/// assert_eq!(cgroup.canonical_name(), "/service/subproc/tier1");
+	/// assert_eq!(CGroup::from_cgroup_path("/../../job").canonical_name(), "/job");
+	/// ```
+	pub fn canonical_name(&self) -> String {
+		let mut parts = Vec::new();
+		for component in self.path.components() {
+			match component {
+				Component::Normal(part) => parts.push(part),
+				Component::ParentDir => {
+					parts.pop();
+				}
+				Component::RootDir | Component::CurDir | Component::Prefix(_) => (),
+			}
+		}
+		let mut name = PathBuf::from("/");
+		name.extend(parts);
+		name.display().to_string()
 	}
 
-	/// Returns true if the cgroup was modified.
+	/// Appends a relative name to this [`CGroup`], or replaces it with an absolute one. Returns true if the cgroup was
+	/// modified.
+	///
+	/// ".." components are kept in the path and resolved by [`CGroup::canonical_name`], so "../sibling" names a
+	/// sibling and ".." stops at the root of the hierarchy.
 	///
 	/// # Examples
 	///
@@ -70,21 +241,127 @@ impl CGroup {
 	/// assert_eq!(cgroup.as_cgroup_path().to_str(), Some("/e"));
 	/// ```
 	pub fn append(&mut self, path: impl AsRef<Path>) -> bool {
-		let new_path = self.0.join(path);
-		if self.0 == new_path {
+		let new_path = self.path.join(path);
+		if self.path == new_path {
 			return false;
 		}
-		self.0 = new_path;
+		self.path = new_path;
+		if let Err(e) = self.validate() {
+			panic!("Error: {e}");
+		}
 		true
 	}
 
-	/// Returns the parent of this [`CGroup`] if there is one.
+	/// Checks the path of this [`CGroup`] against the kernel's limits on file names: each component may be at most
+	/// 255 bytes (`NAME_MAX`), and the full path in the cgroup file system must be shorter than 4096 bytes
+	/// (`PATH_MAX`). "." and ".." components are allowed; they are resolved as in [`CGroup::canonical_name`].
+	pub fn validate(&self) -> Result<(), String> {
+		for component in self.path.components() {
+			if let Component::Normal(name) = component {
+				if name.len() > NAME_MAX {
+					return Err(format!(
+						"Control group name \"{}\" is {} bytes long, but the kernel allows at most {NAME_MAX}",
+						name.to_string_lossy(),
+						name.len()
+					));
+				}
+			}
+		}
+		let path = self.cgroupfs_path();
+		if path.as_os_str().len() >= PATH_MAX {
+			return Err(format!(
+				"Path of control group {self} in the cgroup file system is {} bytes long, but the kernel allows at most {}",
+				path.as_os_str().len(),
+				PATH_MAX - 1
+			));
+		}
+		Ok(())
+	}
+
+	/// Returns the parent of this [`CGroup`], or `None` for the root.
+	///
+	/// "." and ".." components are resolved first, as in [`CGroup::canonical_name`], so "/a/.." has no parent.
 	pub fn parent(&self) -> Option<Self> {
-		self.0.parent().map(Path::to_path_buf).map(Self)
+		let path = Path::new(&self.canonical_name()).parent()?.to_path_buf();
+		Some(Self {
+			path,
+			root: self.root.clone(),
+			dry_run: self.dry_run,
+		})
+	}
+
+	/// Returns the ancestors of this [`CGroup`], from its parent up to the root of the hierarchy.
+	///
+	/// "." and ".." components are resolved first, as in [`CGroup::canonical_name`].
+	pub fn ancestors(&self) -> Vec<CGroup> {
+		let mut ancestors = Vec::new();
+		let mut current = Self {
+			path: PathBuf::from(self.canonical_name()),
+			root: self.root.clone(),
+			dry_run: self.dry_run,
+		};
+		while let Some(parent) = current.parent() {
+			ancestors.push(parent.clone());
+			current = parent;
+		}
+		ancestors
+	}
+
+	/// Returns true if this [`CGroup`] is below `ancestor` in the hierarchy, but not `ancestor` itself.
+	///
+	/// Both names are compared after resolving "." and ".." components, as in [`CGroup::canonical_name`], and whole
+	/// components are compared, so "/a/bc" is not below "/a/b".
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::CGroup;
+	///
+	/// let cgroup = CGroup::from_cgroup_path("/a/b/c");
+	/// assert!(cgroup.is_descendant_of(&CGroup::from_cgroup_path("/a")));
+	/// assert!(cgroup.is_descendant_of(&CGroup::from_cgroup_path("/")));
+	/// assert!(!cgroup.is_descendant_of(&CGroup::from_cgroup_path("/x")));
+	/// assert!(!cgroup.is_descendant_of(&CGroup::from_cgroup_path("/a/b/c/")));
+	/// ```
+	pub fn is_descendant_of(&self, ancestor: &CGroup) -> bool {
+		let name = self.canonical_name();
+		let ancestor = ancestor.canonical_name();
+		name != ancestor && Path::new(&name).starts_with(ancestor)
+	}
+
+	/// Returns true if this [`CGroup`] is `other` or below it in the hierarchy. See [`CGroup::is_descendant_of`].
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::CGroup;
+	///
+	/// let cgroup = CGroup::from_cgroup_path("/a/b/c");
+	/// assert!(cgroup.is_same_or_descendant_of(&CGroup::from_cgroup_path("/a")));
+	/// assert!(cgroup.is_same_or_descendant_of(&CGroup::from_cgroup_path("/a/./b/c/")));
+	/// assert!(!cgroup.is_same_or_descendant_of(&CGroup::from_cgroup_path("/x")));
+	/// ```
+	pub fn is_same_or_descendant_of(&self, other: &CGroup) -> bool {
+		Path::new(&self.canonical_name()).starts_with(other.canonical_name())
 	}
 
-	fn cgroupfs_path(&self) -> PathBuf {
-		Path::new("/sys/fs/cgroup").join(&self.0.strip_prefix("/").unwrap())
+	/// Returns true if the current process is in this [`CGroup`] or one of its descendants.
+	///
+	/// Operations on such a group also affect the calling process. For example, freezing it would suspend the
+	/// caller itself.
+	pub fn contains_current(&self) -> bool {
+		Self::current().is_same_or_descendant_of(self)
+	}
+
+	/// Returns the path of the directory of this [`CGroup`] in the cgroup file system, such as "/sys/fs/cgroup/a/b"
+	/// for "/a/b". The directory may not exist.
+	///
+	/// The path is built from [`CGroup::canonical_name`], so ".." components are resolved against the control group
+	/// rather than the file system, and cannot lead outside of the cgroup file system: "/a/../../etc" is "/etc" in the
+	/// hierarchy, not "/sys/etc".
+	pub fn cgroupfs_path(&self) -> PathBuf {
+		let name = self.canonical_name();
+		self.root.join(name.trim_start_matches('/'))
 	}
 
 	fn cgroupfs_path_if_exists(&self) -> Option<PathBuf> {
@@ -92,41 +369,201 @@ impl CGroup {
 		path.try_exists().unwrap().then_some(path)
 	}
 
-	/// Creates the CGroup on the filesystem if it doesn't exist yet.
-	pub fn create(&self) {
+	/// Returns whether the CGroup exists on the filesystem, without printing or changing anything.
+	///
+	/// Errors other than the group not existing, such as a permission error on an ancestor directory or a path that
+	/// is too long, are returned rather than reported as `false`.
+	pub fn exists(&self) -> io::Result<bool> {
 		let path = self.cgroupfs_path();
-		let exists = path.try_exists().unwrap();
-		if exists {
+		internal::with_io_timeout(move || path.try_exists())
+	}
+
+	/// Creates the CGroup on the filesystem if it doesn't exist yet, along with any missing ancestors.
+	///
+	/// Returns whether the group was newly created, or would have been in a dry run, so that the caller can report it.
+	/// An invalid path is reported as [`io::ErrorKind::InvalidInput`].
+	pub fn create(&self) -> io::Result<bool> {
+		if let Err(e) = self.validate() {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+		}
+		if self.exists()? {
 			// Nothing to do
-			return;
+			return Ok(false);
 		}
-		match fs::create_dir_all(&path) {
-			Ok(()) => (),
-			Err(e) => panic!("Error: While creating control group {self}: {e}"),
+		if self.dry_run {
+			return Ok(true);
+		}
+		let path = self.cgroupfs_path();
+		let result = internal::with_io_timeout(move || fs::create_dir_all(path));
+		audit::record(self, "create", None, None, &result);
+		result?;
+		Ok(true)
+	}
+
+	/// Creates the CGroup like [`CGroup::create`], enabling the given controllers for children of every level above it
+	/// that has to be created, so that the new group can use them right away.
+	///
+	/// The levels are configured top-down, starting from the nearest ancestor that already exists, each one before
+	/// the next is created. `on_created` is called right after each level is created, or would have been in a dry run,
+	/// so that the caller can report it in order with the writes. Returns each configured level with the outcome for
+	/// each controller. Errors creating a level are returned like from [`CGroup::create`], while errors enabling
+	/// controllers panic.
+	#[allow(clippy::type_complexity)] // one level and its outcomes per element
+	pub fn create_with_subtree_control(
+		&self,
+		controllers: &[impl AsRef<str>],
+		mut on_created: impl FnMut(&CGroup),
+	) -> io::Result<Vec<(CGroup, Vec<(String, SetOutcome)>)>> {
+		if let Err(e) = self.validate() {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, e));
+		}
+		let mut levels = self.ancestors();
+		if let Some(nearest) = levels
+			.iter()
+			.position(|level| level.cgroupfs_path_if_exists().is_some())
+		{
+			levels.truncate(nearest + 1);
+		}
+		let mut configured: Vec<(CGroup, Vec<(String, SetOutcome)>)> = Vec::new();
+		for level in levels.into_iter().rev() {
+			if level.create()? {
+				on_created(&level);
+			}
+			// Below the first level, the parent was configured in the previous iteration, so the controllers are
+			// already available. A dry run would otherwise look them up in the parent again and repeat its writes.
+			let parent_configured = !configured.is_empty();
+			let outcomes = controllers
+				.iter()
+				.map(|controller| {
+					let controller = controller.as_ref();
+					let outcome = if parent_configured {
+						level.add_subtree_control(controller)
+					} else {
+						level.enable_subtree_control(controller)
+					};
+					(controller.to_string(), outcome)
+				})
+				.collect();
+			configured.push((level, outcomes));
 		}
-		println!("Notice: Created control group {self}");
+		if self.create()? {
+			on_created(self);
+		}
+		Ok(configured)
 	}
 
 	/// Classifies the given process ID into this [`CGroup`].
+	///
+	/// The process is moved by writing its PID to `cgroup.procs` of the destination, as described in
+	/// <https://docs.kernel.org/admin-guide/cgroup-v2.html#processes>. The file is opened for writing without
+	/// `O_APPEND` or `O_TRUNC`; no seek is required because the kernel treats every write(2) as one command.
 	pub fn classify(&self, pid: u32) {
-		let Some(mut path) = self.cgroupfs_path_if_exists() else {
-			panic!("Error: Control group {self} does not exist");
-		};
-		path.push("cgroup.procs");
-		let mut f = match File::options().append(true).open(&path) {
-			Ok(f) => f,
-			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-				panic!("Error: Permission denied: cannot assign to control group {self}");
+		// In a dry run, the group may be one that would have been created
+		if !self.dry_run && self.cgroupfs_path_if_exists().is_none() {
+			CGroupError::not_found(self).raise();
+		}
+		if let Err(e) = self.try_classify(pid) {
+			CGroupError::from_io(self, "cgroup.procs", e).raise();
+		}
+	}
+
+	/// Classifies the given process ID and all of its descendants into this [`CGroup`], returning the descendants that
+	/// were moved.
+	///
+	/// Children forked after their parent has moved start in this group, but a process can fork between the walk of
+	/// the process tree and its own move, so the walk is repeated up to [`MAX_TREE_WALKS`](Self::MAX_TREE_WALKS)
+	/// times until it finds no process left behind. Descendants that exit before they are moved are skipped.
+	pub fn classify_with_descendants(&self, pid: u32) -> Vec<u32> {
+		self.classify(pid);
+		let mut moved = Vec::new();
+		for _ in 0..Self::MAX_TREE_WALKS {
+			let descendants = match internal::descendant_pids(pid) {
+				Ok(descendants) => descendants,
+				Err(e) => panic!("Error: While listing the descendants of process {pid}: {e}"),
+			};
+			let pending = descendants
+				.into_iter()
+				.filter(|descendant| !moved.contains(descendant))
+				.collect::<Vec<_>>();
+			if pending.is_empty() {
+				break;
 			}
-			Err(e) => panic!("Error: While assigning {pid} to control group {self}: {e}"),
-		};
-		match write!(&mut f, "{}", pid) {
-			Ok(()) => (),
-			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-				panic!("Error: Permission denied: cannot detach process from existing cgroup");
+			for descendant in pending {
+				match self.try_classify(descendant) {
+					Ok(()) => moved.push(descendant),
+					Err(e) if e.raw_os_error() == Some(libc::ESRCH) => continue,
+					Err(e) => CGroupError::from_io(self, "cgroup.procs", e).raise(),
+				}
+			}
+		}
+		moved.sort_unstable();
+		moved
+	}
+
+	/// Classifies the given process ID into this [`CGroup`] like [`CGroup::classify`], then confirms that it is listed
+	/// in `cgroup.procs`.
+	///
+	/// The kernel can accept the write without moving the process, for example for some kernel threads. Returns an
+	/// error if the process is not listed afterwards, which also happens if it exited in the meantime.
+	pub fn move_and_verify(&self, pid: u32) -> Result<(), String> {
+		self.classify(pid);
+		if self.procs().contains(&pid) {
+			Ok(())
+		} else {
+			Err(format!("Process {pid} is not in control group {self} after moving it. The kernel may have declined to move it, or it may have exited."))
+		}
+	}
+
+	/// Writes a PID to `cgroup.procs` of this [`CGroup`], retrying while the kernel reports it as busy.
+	fn try_classify(&self, pid: u32) -> io::Result<()> {
+		let path = self.cgroupfs_path().join("cgroup.procs");
+		if self.skip_write(&path, &pid.to_string()) {
+			return Ok(());
+		}
+		let result = internal::with_io_timeout(move || File::options().write(true).open(path)).and_then(|f| {
+			retry_on_busy(
+				|| {
+					let mut f = f.try_clone()?;
+					internal::with_io_timeout(move || write_pid(&mut f, pid))
+				},
+				|delay| println!("Notice: Control group {self} is busy; retrying assignment of {pid} in {delay:?}"),
+			)
+		});
+		audit::record(self, "classify", Some("cgroup.procs"), Some(&pid.to_string()), &result);
+		result
+	}
+
+	/// Moves the processes currently in this [`CGroup`] to `dest` in a single pass, returning the PIDs that were
+	/// moved.
+	///
+	/// The list of processes is read once, so processes that are forked or moved into this group during the pass
+	/// stay behind. Processes that exit before they are moved are skipped. Only processes directly in this group
+	/// are moved, not those in its descendants.
+	pub fn copy_procs_to(&self, dest: &CGroup) -> Vec<u32> {
+		if dest.cgroupfs_path_if_exists().is_none() {
+			CGroupError::not_found(dest).raise();
+		}
+		let mut moved = Vec::new();
+		for pid in self.procs() {
+			match dest.try_classify(pid) {
+				Ok(()) => moved.push(pid),
+				Err(e) if e.raw_os_error() == Some(libc::ESRCH) => continue,
+				Err(e) => CGroupError::from_io(dest, "cgroup.procs", e).raise(),
 			}
-			Err(e) => panic!("Error: While assigning {pid} to control group {self}: {e}"),
 		}
+		moved
+	}
+
+	/// Starts a command as a child process directly in this [`CGroup`] and returns its process ID.
+	///
+	/// Unlike classifying a process after it starts, the child never runs outside of this control group, and the
+	/// current process stays where it is. The child must be reaped with [`internal::wait_pid`]. Requires Linux 5.7 or
+	/// newer; older kernels return an [`io::ErrorKind::Unsupported`] error, in which case the caller may fall back
+	/// to [`CGroup::classify_current`] before starting the command.
+	pub fn spawn_into(&self, command: &[OsString]) -> io::Result<u32> {
+		let path = self.cgroupfs_path();
+		let dir = internal::with_io_timeout(move || File::open(path))?;
+		internal::spawn_into_cgroup(&dir, command)
 	}
 
 	/// Classifies the current process into this [`CGroup`].
@@ -137,117 +574,3131 @@ impl CGroup {
 	/// Loads the controllers enabled for this [`CGroup`].
 	pub fn controllers(&self) -> Vec<String> {
 		let Some(mut path) = self.cgroupfs_path_if_exists() else {
-			panic!("Error: Control group {self} does not exist");
+			CGroupError::not_found(self).raise();
 		};
 		path.push("cgroup.controllers");
-		let mut f = match File::options().read(true).open(&path) {
-			Ok(f) => f,
-			Err(e) => panic!("Error: While loading the controllers of {self}: {e}"),
+		let contents = match internal::with_io_timeout(move || fs::read_to_string(path)) {
+			Ok(contents) => contents,
+			Err(e) => CGroupError::from_io(self, "cgroup.controllers", e).raise(),
 		};
-		let mut contents = String::new();
-		f.read_to_string(&mut contents).unwrap();
-		contents.trim().split_whitespace().map(ToString::to_string).collect()
+		contents.split_whitespace().map(ToString::to_string).collect()
 	}
 
-	/// Returns whether the cgroup directly owns any processes.
-	pub fn has_processes(&self) -> bool {
-		let Some(mut path) = self.cgroupfs_path_if_exists() else {
-			panic!("Error: Control group {self} does not exist");
-		};
-		path.push("cgroup.procs");
-		let mut f = match File::options().read(true).open(&path) {
-			Ok(f) => f,
-			Err(e) => panic!("Error: While loading the processes of {self}: {e}"),
+	/// Returns the well-known controllers and any others that the kernel provides, with whether the kernel, this
+	/// [`CGroup`], and its children may use each one.
+	///
+	/// The well-known controllers come first, followed by other controllers in the order of `cgroup.controllers` of
+	/// the root.
+	pub fn list_controllers_with_availability(&self) -> Vec<ControllerStatus> {
+		let root = Self {
+			path: PathBuf::from("/"),
+			root: self.root.clone(),
+			dry_run: self.dry_run,
 		};
-		let mut contents = String::new();
-		f.read_to_string(&mut contents).unwrap();
-		!contents.trim().is_empty()
+		let at_root = root.controllers();
+		let available = self.controllers();
+		let in_subtree = self.subtree_controllers();
+		let mut names = KNOWN_CONTROLLERS.iter().map(ToString::to_string).collect::<Vec<_>>();
+		names.extend(
+			at_root
+				.iter()
+				.filter(|name| !KNOWN_CONTROLLERS.contains(&name.as_str()))
+				.cloned(),
+		);
+		names
+			.into_iter()
+			.map(|name| ControllerStatus {
+				available_at_root: at_root.contains(&name),
+				available: available.contains(&name),
+				in_subtree: in_subtree.contains(&name),
+				name,
+			})
+			.collect()
 	}
 
-	/// Allow children of the current [`CGroup`] to set restrictions on the given controllers.
-	pub fn enable_subtree_control(&self, controller: &str) {
-		if self.has_processes() {
-			println!("Warning: Control group {self} owns one or more processes. Enabling controllers in children of nonempty control groups can cause unexpected behavior. For example, a domain cgroup might turned into a threaded domain. See <https://docs.kernel.org/admin-guide/cgroup-v2.html>")
+	/// Loads the controllers that children of this [`CGroup`] may use, from `cgroup.subtree_control`.
+	pub fn subtree_controllers(&self) -> Vec<String> {
+		let contents = self.read_interface_file("cgroup.subtree_control");
+		contents.split_whitespace().map(ToString::to_string).collect()
+	}
+
+	/// Returns whether the given controller is enabled for this [`CGroup`], that is, listed in
+	/// `cgroup.controllers`.
+	///
+	/// ```no_run
+	/// use cg2tools::CGroup;
+	///
+	/// let cgroup = CGroup::from_cgroup_path("/job");
+	/// if !cgroup.is_controller_enabled("memory") {
+	///     cgroup.enable_controller("memory");
+	/// }
+	/// ```
+	pub fn is_controller_enabled(&self, name: &str) -> bool {
+		self.controllers().iter().any(|c| c == name)
+	}
+
+	/// Returns whether the given controller is enabled for the children of this [`CGroup`], that is, listed in
+	/// `cgroup.subtree_control`.
+	///
+	/// ```no_run
+	/// use cg2tools::CGroup;
+	///
+	/// let parent = CGroup::from_cgroup_path("/service");
+	/// assert!(parent.is_controller_in_subtree("cpu"));
+	/// ```
+	pub fn is_controller_in_subtree(&self, name: &str) -> bool {
+		self.subtree_controllers().iter().any(|c| c == name)
+	}
+
+	/// Returns each level of the hierarchy from the root down to this [`CGroup`], with whether the given controller is
+	/// enabled for the children of that level in `cgroup.subtree_control`.
+	///
+	/// A group can only use a controller if every level above it enables the controller for its children, so the
+	/// first level without it is where delegation breaks.
+	pub fn delegation_chain(&self, controller: &str) -> Vec<(CGroup, bool)> {
+		let mut levels = self.ancestors();
+		levels.reverse();
+		levels.push(Self {
+			path: PathBuf::from(self.canonical_name()),
+			root: self.root.clone(),
+			dry_run: self.dry_run,
+		});
+		levels
+			.into_iter()
+			.map(|level| {
+				let enabled = level.is_controller_in_subtree(controller);
+				(level, enabled)
+			})
+			.collect()
+	}
+
+	/// Returns whether this [`CGroup`] or any of its descendants contains a live process, according to the
+	/// "populated" field of `cgroup.events`.
+	pub fn is_populated(&self) -> bool {
+		self.read_event_flag("populated")
+	}
+
+	/// Returns whether this [`CGroup`] is frozen, according to the "frozen" field of `cgroup.events`.
+	pub fn is_frozen(&self) -> bool {
+		self.read_event_flag("frozen")
+	}
+
+	/// Reads a boolean field of `cgroup.events`, such as "populated".
+	fn read_event_flag(&self, field: &str) -> bool {
+		self.read_events("cgroup.events").get(field) == Some(&1)
+	}
+
+	/// Reads a flat keyed file with one "key value" pair per line, such as `cgroup.events`, `memory.events`, or
+	/// `pids.events`. Lines without a numeric value are skipped.
+	pub fn read_events(&self, file: &str) -> HashMap<String, u64> {
+		events::parse(&self.read_interface_file(file))
+	}
+
+	/// Blocks until neither this [`CGroup`] nor any of its descendants contains a live process, or until the timeout
+	/// elapses.
+	///
+	/// The kernel signals changes to `cgroup.events` with `POLLPRI`, so this usually wakes up as soon as the last
+	/// process exits. Polls are capped at [`EMPTY_POLL_INTERVAL`](Self::EMPTY_POLL_INTERVAL), so the file is also
+	/// re-read periodically where notifications are unavailable.
+	pub fn wait_until_empty(&self, timeout: Duration) -> Result<(), TimeoutError> {
+		if self.wait_for_event_flag("populated", false, timeout) {
+			Ok(())
+		} else {
+			Err(TimeoutError {
+				cgroup: self.to_string(),
+				timeout,
+			})
 		}
-		self.enable_controller(controller);
-		let Some(mut path) = self.cgroupfs_path_if_exists() else {
-			panic!("Error: Control group {self} does not exist");
+	}
+
+	/// Blocks until `cgroup.events` reports this [`CGroup`] as frozen or thawed, as requested, or until the timeout
+	/// elapses. Returns whether the state was reached.
+	///
+	/// Freezing is asynchronous, so [`CGroup::set_frozen`] returns before every process has stopped. Like
+	/// [`CGroup::wait_until_empty`], this wakes up on notifications from the kernel.
+	pub fn wait_until_frozen(&self, frozen: bool, timeout: Duration) -> bool {
+		self.wait_for_event_flag("frozen", frozen, timeout)
+	}
+
+	/// Blocks until a boolean field of `cgroup.events` has the given value, returning false if the timeout elapses
+	/// first.
+	fn wait_for_event_flag(&self, field: &str, value: bool, timeout: Duration) -> bool {
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
 		};
-		path.push("cgroup.subtree_control");
-		let mut f = match File::options().append(true).open(&path) {
+		let f = match internal::with_io_timeout(move || File::open(path.join("cgroup.events"))) {
 			Ok(f) => f,
-			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-				panic!("Error: Permission denied: cannot change cgroup.subtree_control for control group {self}");
-			}
-			Err(e) => panic!("Error: Opening {path:?}: {e}"),
+			Err(e) => CGroupError::from_io(self, "cgroup.events", e).raise(),
 		};
-		// It seems that this needs to be written as one chunk
-		let str_to_write = format!("+{controller}");
-		match write!(&mut f, "{str_to_write}") {
-			Ok(()) => {
-				println!("Notice: Enabled controller \"{controller}\" for subgroups of {self}");
+		let deadline = Instant::now() + timeout;
+		loop {
+			// Reading from the start also acknowledges the notification that woke us up
+			let read = f.try_clone().and_then(|mut f| {
+				internal::with_io_timeout(move || {
+					let mut contents = String::new();
+					f.rewind()?;
+					f.read_to_string(&mut contents)?;
+					Ok(contents)
+				})
+			});
+			let contents = match read {
+				Ok(contents) => contents,
+				Err(e) => CGroupError::from_io(self, "cgroup.events", e).raise(),
+			};
+			if (events::parse(&contents).get(field) == Some(&1)) == value {
+				return true;
 			}
-			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-				panic!("Error: Permission denied: cannot enable controller \"{controller}\" in control group {self}");
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if remaining.is_zero() {
+				return false;
+			}
+			let mut fds = [libc::pollfd {
+				fd: f.as_raw_fd(),
+				events: libc::POLLPRI,
+				revents: 0,
+			}];
+			let wait = remaining.min(Self::EMPTY_POLL_INTERVAL).as_millis() as libc::c_int;
+			// SAFETY: `fds` is a valid array of one pollfd, and the file stays open for the duration of the call
+			if unsafe { libc::poll(fds.as_mut_ptr(), 1, wait) } < 0 {
+				let e = io::Error::last_os_error();
+				if e.kind() != io::ErrorKind::Interrupted {
+					CGroupError::from_io(self, "cgroup.events", e).raise();
+				}
 			}
-			Err(e) => panic!("Error: Writing to {path:?}: {e}"),
 		}
 	}
 
-	/// Allow the current [`CGroup`] to set restrictions on the given controllers.
-	pub fn enable_controller(&self, controller: &str) {
-		let current_controllers = self.controllers();
-		if current_controllers.iter().any(|c| c == controller) {
-			// Nothing to do
-			return;
+	/// Freezes or thaws all processes in this [`CGroup`] and its descendants by writing to `cgroup.freeze`.
+	///
+	/// The kernel stops the processes asynchronously, so they may still be running when this returns. Use
+	/// [`CGroup::wait_until_frozen`] to wait for the new state.
+	pub fn set_frozen(&self, frozen: bool) -> io::Result<()> {
+		let path = self.cgroupfs_path().join("cgroup.freeze");
+		let value = if frozen { "1" } else { "0" };
+		if self.skip_write(&path, value) {
+			return Ok(());
 		}
-		let Some(parent) = self.parent() else {
-			panic!("Error: Controller \"{controller}\" not available on this system");
+		// Truncated like a shell redirect, but not created if missing, so that a missing group is reported as not found
+		let result = internal::with_io_timeout(move || {
+			File::options()
+				.write(true)
+				.truncate(true)
+				.open(path)
+				.and_then(|mut f| f.write_all(value.as_bytes()))
+		});
+		audit::record(self, "set", Some("cgroup.freeze"), Some(value), &result);
+		result
+	}
+
+	/// Kills all processes in this [`CGroup`] and its descendants with SIGKILL by writing to `cgroup.kill`.
+	///
+	/// Kernels before 5.14 have no `cgroup.kill`, so each process listed in the groups is killed instead. Unlike the
+	/// kernel's implementation, this can miss processes that are forked while the groups are walked.
+	pub fn kill_all(&self) -> io::Result<()> {
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			return Err(io::ErrorKind::NotFound.into());
 		};
-		parent.enable_subtree_control(controller);
+		let path = path.join("cgroup.kill");
+		if self.skip_write(&path, "1") {
+			return Ok(());
+		}
+		// Not created if missing, so that older kernels are detected by the file being not found
+		let result = internal::with_io_timeout(move || {
+			File::options()
+				.write(true)
+				.open(path)
+				.and_then(|mut f| f.write_all(b"1"))
+		});
+		audit::record(self, "set", Some("cgroup.kill"), Some("1"), &result);
+		match result {
+			Err(e) if e.kind() == io::ErrorKind::NotFound => {
+				println!("Notice: cgroup.kill is unavailable (it requires Linux 5.14 or newer), so the processes of control group {self} are killed one by one");
+			}
+			result => return result,
+		}
+		for cgroup in std::iter::once(self.clone()).chain(self.descendants(1)) {
+			for pid in cgroup.procs() {
+				// SAFETY: kill has no memory safety requirements
+				if unsafe { libc::kill(pid as libc::pid_t, libc::SIGKILL) } < 0 {
+					let e = io::Error::last_os_error();
+					// The process exited in the meantime
+					if e.raw_os_error() != Some(libc::ESRCH) {
+						return Err(e);
+					}
+				}
+			}
+		}
+		Ok(())
 	}
 
-	/// Allow the current [`CGroup`] to set the given restriction.
-	pub fn enable_controller_for_restriction(&self, key: &str) {
-		let controller = key.split_once('.').unwrap().0;
-		self.enable_controller(controller)
+	/// Returns the type of this [`CGroup`] from `cgroup.type`.
+	///
+	/// The root control group has no `cgroup.type` file and is always reported as [`CGroupType::Domain`].
+	pub fn cgroup_type(&self) -> CGroupType {
+		let Some(contents) = self.read_optional_interface_file("cgroup.type") else {
+			return CGroupType::Domain;
+		};
+		match CGroupType::parse(&contents) {
+			Some(cgroup_type) => cgroup_type,
+			None => panic!("Error: Unknown type {:?} of control group {self}", contents.trim()),
+		}
 	}
 
-	/// Sets a restriction based on the key (file name, like "cpu.max") and value (like "90000 100000").
+	/// Turns this [`CGroup`] into a member of a threaded subtree by writing "threaded" to `cgroup.type`.
 	///
-	/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html>
-	pub fn set_restriction(&self, key: &str, value: &str) {
-		let Some(mut path) = self.cgroupfs_path_if_exists() else {
-			panic!("Error: Control group {self} does not exist");
+	/// The parent becomes [`CGroupType::DomainThreaded`] if it is a domain. The kernel rejects the change, for example
+	/// with EOPNOTSUPP, if the group has domain controllers enabled or its parent cannot become a threaded domain. A
+	/// threaded group cannot be turned back into a domain.
+	pub fn set_threaded(&self) -> io::Result<()> {
+		let path = self.cgroupfs_path().join("cgroup.type");
+		if self.skip_write(&path, "threaded") {
+			return Ok(());
+		}
+		// Truncated like a shell redirect, but not created if missing, so that a missing group or the root control group
+		// is reported as not found
+		let result = internal::with_io_timeout(move || {
+			File::options()
+				.write(true)
+				.truncate(true)
+				.open(path)
+				.and_then(|mut f| f.write_all(b"threaded"))
+		});
+		audit::record(self, "set", Some("cgroup.type"), Some("threaded"), &result);
+		result
+	}
+
+	/// Returns the IDs of the processes with at least one thread directly in this [`CGroup`].
+	///
+	/// For domain groups, this is `cgroup.procs`. In a threaded group, `cgroup.procs` only lists processes whose
+	/// main thread is in the group, so the thread IDs in `cgroup.threads` are mapped to their processes instead.
+	/// Threads that exit while being mapped are skipped.
+	pub fn procs(&self) -> Vec<u32> {
+		if self.cgroupfs_path_if_exists().is_none() {
+			CGroupError::not_found(self).raise();
+		}
+		match self.read_processes() {
+			Ok(pids) => pids,
+			Err((name, e)) if e.kind() == io::ErrorKind::InvalidData => {
+				panic!("Error: Unexpected format in {name} of control group {self}: {e}")
+			}
+			Err((name, e)) => self.file_error(name, e).raise(),
+		}
+	}
+
+	/// Returns the same processes as [`CGroup::procs`], but returns errors instead of panicking.
+	pub fn processes(&self) -> io::Result<Vec<u32>> {
+		self.read_processes().map_err(|(_, e)| e)
+	}
+
+	/// Lists the processes for [`CGroup::procs`] and [`CGroup::processes`]. Errors come with the interface file that
+	/// caused them.
+	fn read_processes(&self) -> Result<Vec<u32>, (&'static str, io::Error)> {
+		let read = |name: &'static str| {
+			let path = self.cgroupfs_path().join(name);
+			internal::with_io_timeout(move || fs::read_to_string(path)).map_err(|e| (name, e))
 		};
-		path.push(key);
-		let mut f = match File::options().write(true).open(&path) {
-			Ok(f) => f,
-			Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
-				panic!("Error: Permission denied: cannot set restriction {key} in control group {self}");
+		// The root control group has no cgroup.type and is a domain
+		let cgroup_type = match read("cgroup.type") {
+			Ok(contents) => match CGroupType::parse(&contents) {
+				Some(cgroup_type) => cgroup_type,
+				None => {
+					let e = io::Error::new(io::ErrorKind::InvalidData, format!("unknown type {:?}", contents.trim()));
+					return Err(("cgroup.type", e));
+				}
+			},
+			Err((_, e)) if e.kind() == io::ErrorKind::NotFound => CGroupType::Domain,
+			Err(e) => return Err(e),
+		};
+		match cgroup_type {
+			CGroupType::Threaded => {
+				let proc_root = internal::proc_root();
+				let mut pids = parse_ids("cgroup.threads", &read("cgroup.threads")?)?
+					.into_iter()
+					.filter_map(|tid| thread_group_id(&proc_root, tid))
+					.collect::<Vec<_>>();
+				pids.sort_unstable();
+				pids.dedup();
+				Ok(pids)
 			}
-			Err(e) if e.kind() == io::ErrorKind::NotFound => {
-				panic!("Error: Restriction {key} is unavailable for control group {self}");
+			_ => parse_ids("cgroup.procs", &read("cgroup.procs")?),
+		}
+	}
+
+	/// Returns the IDs of the processes in this [`CGroup`] and all of its descendants, sorted.
+	///
+	/// Descendants that are removed during the walk are skipped. A process that moves between groups during the
+	/// walk may be missed or, if it is seen twice, is listed once.
+	pub fn processes_recursive(&self) -> io::Result<Vec<u32>> {
+		let mut pids = self.processes()?;
+		for descendant in self.descendants(1) {
+			match descendant.processes() {
+				Ok(more) => pids.extend(more),
+				Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+				Err(e) => return Err(e),
 			}
-			Err(e) => panic!("Error: {e}"),
+		}
+		pids.sort_unstable();
+		pids.dedup();
+		Ok(pids)
+	}
+
+	/// Returns whether the cgroup directly owns any processes.
+	///
+	/// In a threaded group, this checks `cgroup.threads`, since a process can have threads in the group without
+	/// appearing in its `cgroup.procs`.
+	pub fn has_processes(&self) -> bool {
+		let name = match self.cgroup_type() {
+			CGroupType::Threaded => "cgroup.threads",
+			_ => "cgroup.procs",
 		};
-		match write!(&mut f, "{}", value) {
-			Ok(()) => {
-				println!("Notice: Restriction {key}=\"{value}\" set in control group {self}");
+		!self.read_interface_file(name).trim().is_empty()
+	}
+
+	/// Returns the cumulative time in microseconds that some and all tasks in this [`CGroup`] were stalled waiting
+	/// for CPU, from the "total" fields of `cpu.pressure`.
+	///
+	/// Unlike the decaying averages in the same file, these counters only increase, so the difference between two
+	/// readings gives the stall time during the interval. The second value is 0 on kernels that don't report "full"
+	/// CPU pressure.
+	pub fn cpu_pressure_total(&self) -> (u64, u64) {
+		let contents = self.read_interface_file("cpu.pressure");
+		match pressure::parse_totals(&contents) {
+			Ok(totals) => totals,
+			Err(e) => panic!("Error: Unexpected format in cpu.pressure of control group {self}: {e}"),
+		}
+	}
+
+	/// Lists the interface files of this [`CGroup`], such as "cgroup.procs" and "cpu.max", in sorted order.
+	///
+	/// Subdirectories (child control groups) are not included. Which files exist depends on the controllers
+	/// enabled for the group and on the kernel version.
+	pub fn interface_files(&self) -> Vec<String> {
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		match list_dir_entries(&path, false) {
+			Ok(names) => names,
+			Err(e) => panic!("Error: While listing the interface files of {self}: {e}"),
+		}
+	}
+
+	/// Reads the controllers and every readable interface file of this [`CGroup`] at once, for example to compare
+	/// its state before and after a change.
+	///
+	/// Files that trigger actions rather than hold state, such as `cgroup.procs`, and files that cannot be read are
+	/// left out.
+	pub fn snapshot(&self) -> CGroupSnapshot {
+		let mut snapshot = CGroupSnapshot {
+			cgroup: self.canonical_name(),
+			controllers: self.controllers(),
+			subtree_control: self.subtree_controllers(),
+			..Default::default()
+		};
+		for file in self.interface_files() {
+			if is_action_file(&file) || file == "cgroup.controllers" || file == "cgroup.subtree_control" {
+				continue;
+			}
+			let Ok(value) = self.get_restriction(&file) else {
+				continue;
+			};
+			if self.is_writable(&file) {
+				snapshot.settings.insert(file, value);
+			} else {
+				snapshot.stats.insert(file, value);
 			}
-			Err(e) => panic!("Error: While writing to {path:?}: {e}"),
 		}
+		snapshot
 	}
-}
 
-impl AsRef<Path> for CGroup {
-	fn as_ref(&self) -> &Path {
-		&self.0
+	/// Summarizes the configuration of this [`CGroup`] in one sentence of prose, such as "Control group /job can use
+	/// the cpu and memory controllers, has a memory limit of 2.0GiB (currently using 400.0MiB), has no CPU limit,
+	/// and holds 3 processes."
+	///
+	/// The wording may change between versions. Use [`CGroup::snapshot`] to inspect the configuration from code.
+	pub fn describe(&self) -> String {
+		let controllers = self.controllers();
+		let mut clauses = vec![match controllers.len() {
+			0 => "can use no controllers".to_string(),
+			1 => format!("can use the {} controller", controllers[0]),
+			_ => format!("can use the {} controllers", join_prose(&controllers)),
+		}];
+		if let Some(max) = self.read_optional_interface_file("memory.max") {
+			let max = self.parse_value::<Limit>("memory.max", &max);
+			let current = internal::format_bytes(Limit::Value(self.memory_current()));
+			clauses.push(match max {
+				Limit::Max => format!("has no memory limit (currently using {current})"),
+				Limit::Value(_) => format!(
+					"has a memory limit of {} (currently using {current})",
+					internal::format_bytes(max)
+				),
+			});
+		}
+		if let Some(max) = self.read_optional_interface_file("cpu.max") {
+			clauses.push(match max.split_whitespace().collect::<Vec<_>>()[..] {
+				[quota, period] if quota != "max" => match (quota.parse::<f64>(), period.parse::<f64>()) {
+					(Ok(quota), Ok(period)) if period > 0.0 => {
+						let cpus = format!("{:.2}", quota / period);
+						let cpus = cpus.trim_end_matches('0').trim_end_matches('.');
+						format!("has a CPU limit of {cpus} {}", if cpus == "1" { "CPU" } else { "CPUs" })
+					}
+					_ => panic!("Error: Unexpected format in cpu.max of control group {self}: {max}"),
+				},
+				_ => "has no CPU limit".to_string(),
+			});
+		}
+		clauses.push(match self.procs().len() {
+			0 => "holds no processes".to_string(),
+			1 => "holds 1 process".to_string(),
+			n => format!("holds {n} processes"),
+		});
+		format!("Control group {} {}.", self.canonical_name(), join_prose(&clauses))
 	}
-}
 
-impl fmt::Display for CGroup {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-		self.0.display().fmt(f)
+	/// Returns whether an interface file of this [`CGroup`] has any write permission bits set.
+	fn is_writable(&self, file: &str) -> bool {
+		let path = self.cgroupfs_path().join(file);
+		internal::with_io_timeout(move || fs::metadata(path))
+			.map(|metadata| metadata.permissions().mode() & 0o222 != 0)
+			.unwrap_or(false)
+	}
+
+	/// Lists the names of the child control groups of this [`CGroup`], in sorted order.
+	fn child_names(&self) -> io::Result<Vec<String>> {
+		list_dir_entries(&self.cgroupfs_path(), true)
+	}
+
+	/// Returns the number of descendant control groups of this [`CGroup`] from the "nr_descendants" field of
+	/// `cgroup.stat`, without walking the hierarchy.
+	pub fn nr_descendants(&self) -> u64 {
+		match self.read_events("cgroup.stat").get("nr_descendants") {
+			Some(count) => *count,
+			None => panic!("Error: Unexpected format in cgroup.stat of control group {self}: missing nr_descendants"),
+		}
 	}
+
+	/// Reads the counts of descendant control groups of this [`CGroup`] from `cgroup.stat`.
+	pub fn stat(&self) -> io::Result<CGroupStat> {
+		let path = self.cgroupfs_path().join("cgroup.stat");
+		let contents = internal::with_io_timeout(move || fs::read_to_string(path))?;
+		CGroupStat::from_stat(&events::parse(&contents)).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	/// Returns the children of this [`CGroup`] with their `cpu.weight` and their proportional share of the CPU time
+	/// of this group, as a percentage: the weight divided by the sum of the weights of all children.
+	///
+	/// Children without `cpu.weight`, because the cpu controller is not enabled for children of this group, have
+	/// `None` and are left out of the sum.
+	pub fn weight_tree(&self) -> Vec<(CGroup, Option<(u64, f64)>)> {
+		let weights = self
+			.children()
+			.into_iter()
+			.map(|child| {
+				let weight = child
+					.read_optional_interface_file("cpu.weight")
+					.map(|contents| child.parse_value::<u64>("cpu.weight", &contents));
+				(child, weight)
+			})
+			.collect::<Vec<_>>();
+		let total = weights.iter().filter_map(|(_, weight)| *weight).sum::<u64>();
+		weights
+			.into_iter()
+			.map(|(child, weight)| {
+				let share = weight.map(|weight| (weight, weight as f64 * 100.0 / total as f64));
+				(child, share)
+			})
+			.collect()
+	}
+
+	/// Returns the child control groups of this [`CGroup`], in sorted order.
+	pub fn children(&self) -> Vec<CGroup> {
+		match self.child_names() {
+			Ok(names) => names.iter().map(|name| self.join(name)).collect(),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => CGroupError::not_found(self).raise(),
+			Err(e) => panic!("Error: While listing the children of {self}: {e}"),
+		}
+	}
+
+	/// Returns all descendants of this [`CGroup`] (not including itself), sorted by path so that every group comes
+	/// directly before its own descendants.
+	///
+	/// Directory reads are I/O bound, so for large hierarchies each level of the tree can be read by up to `jobs`
+	/// threads in parallel. The number of threads is capped at [`MAX_JOBS`](Self::MAX_JOBS) regardless of the
+	/// fan-out of the tree. Groups that are removed during the walk are skipped.
+	pub fn descendants(&self, jobs: usize) -> Vec<CGroup> {
+		let jobs = jobs.clamp(1, Self::MAX_JOBS);
+		let mut descendants = Vec::new();
+		let mut level = vec![self.clone()];
+		while !level.is_empty() {
+			let chunk_size = level.len().div_ceil(jobs);
+			let results = thread::scope(|scope| {
+				let handles = level
+					.chunks(chunk_size)
+					.map(|chunk| scope.spawn(move || chunk.iter().map(|c| (c, c.child_names())).collect::<Vec<_>>()))
+					.collect::<Vec<_>>();
+				handles.into_iter().flat_map(|h| h.join().unwrap()).collect::<Vec<_>>()
+			});
+			let mut next_level = Vec::new();
+			for (cgroup, names) in results {
+				match names {
+					Ok(names) => next_level.extend(names.iter().map(|name| cgroup.join(name))),
+					Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+					Err(e) => panic!("Error: While listing the children of {cgroup}: {e}"),
+				}
+			}
+			descendants.extend(next_level.iter().cloned());
+			level = next_level;
+		}
+		descendants.sort_by(|a, b| a.path.cmp(&b.path));
+		descendants
+	}
+
+	/// Returns the time this [`CGroup`] was created, based on the modification time of its directory.
+	///
+	/// The kernel sets the modification time when the group is created, but it may also be updated later, for
+	/// example when child groups are created or removed. Treat it as an approximation of the age of the group.
+	pub fn created_at(&self) -> io::Result<SystemTime> {
+		let path = self.cgroupfs_path();
+		internal::with_io_timeout(move || fs::metadata(path))?.modified()
+	}
+
+	/// Removes this [`CGroup`] from the file system.
+	///
+	/// The kernel only removes control groups that have no child groups and no live processes.
+	pub fn remove(&self) -> io::Result<()> {
+		if self.dry_run {
+			println!("Would remove directory {}", self.cgroupfs_path().display());
+			return Ok(());
+		}
+		let path = self.cgroupfs_path();
+		let result = internal::with_io_timeout(move || fs::remove_dir(path));
+		audit::record(self, "remove", None, None, &result);
+		result
+	}
+
+	/// Returns the child of this [`CGroup`] with the given name.
+	fn join(&self, name: &str) -> Self {
+		Self {
+			path: self.path.join(name),
+			root: self.root.clone(),
+			dry_run: self.dry_run,
+		}
+	}
+
+	/// Reads an interface file of this [`CGroup`], such as "memory.current".
+	fn read_interface_file(&self, name: &str) -> String {
+		let Some(mut path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		path.push(name);
+		match internal::with_io_timeout(move || fs::read_to_string(path)) {
+			Ok(contents) => contents,
+			Err(e) => self.file_error(name, e).raise(),
+		}
+	}
+
+	/// Classifies an error accessing an interface file. A missing file of a controller is checked against the
+	/// parent's `cgroup.subtree_control`, to tell whether the controller needs to be enabled there.
+	fn file_error(&self, file: &str, e: io::Error) -> CGroupError {
+		if e.kind() == io::ErrorKind::NotFound {
+			if let (Some(controller), Some(parent)) = (controller_for_key(file), self.parent()) {
+				if !parent.is_controller_in_subtree(controller) {
+					return CGroupError::ControllerNotEnabled {
+						cgroup: self.to_string(),
+						file: file.to_string(),
+						controller: controller.to_string(),
+						parent: parent.to_string(),
+					};
+				}
+			}
+		}
+		CGroupError::from_io(self, file, e)
+	}
+
+	/// In a dry run, prints the write of `contents` to the interface file at `path` instead of making it, and returns
+	/// true.
+	fn skip_write(&self, path: &Path, contents: &str) -> bool {
+		if !self.dry_run {
+			return false;
+		}
+		println!("Would write {contents:?} to {}", path.display());
+		true
+	}
+
+	/// Reads an interface file of this [`CGroup`], returning `None` if the kernel does not provide it.
+	fn read_optional_interface_file(&self, name: &str) -> Option<String> {
+		let Some(mut path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		path.push(name);
+		match internal::with_io_timeout(move || fs::read_to_string(path)) {
+			Ok(contents) => Some(contents),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+			Err(e) => CGroupError::from_io(self, name, e).raise(),
+		}
+	}
+
+	/// Parses the contents of an interface file that holds a single value.
+	fn parse_value<T: FromStr>(&self, name: &str, contents: &str) -> T
+	where
+		T::Err: fmt::Display,
+	{
+		match contents.trim().parse() {
+			Ok(value) => value,
+			Err(e) => panic!("Error: Unexpected format in {name} of control group {self}: {e}"),
+		}
+	}
+
+	/// Reads an interface file that holds a single value, such as "memory.current".
+	fn read_value<T: FromStr>(&self, name: &str) -> T
+	where
+		T::Err: fmt::Display,
+	{
+		self.parse_value(name, &self.read_interface_file(name))
+	}
+
+	/// Reads an interface file that holds a single value, returning `None` if the kernel does not provide it.
+	fn read_optional_value<T: FromStr>(&self, name: &str) -> Option<T>
+	where
+		T::Err: fmt::Display,
+	{
+		let contents = self.read_optional_interface_file(name)?;
+		Some(self.parse_value(name, &contents))
+	}
+
+	/// Returns the total memory in bytes currently used by this [`CGroup`] and its descendants.
+	pub fn memory_current(&self) -> u64 {
+		self.read_value("memory.current")
+	}
+
+	/// Returns the highest swap usage in bytes recorded for this [`CGroup`], if the kernel provides it.
+	pub fn memory_swap_peak(&self) -> Option<u64> {
+		self.read_optional_value("memory.swap.peak")
+	}
+
+	/// Returns the bytes of compressed memory this [`CGroup`] holds in zswap, if zswap is available.
+	pub fn memory_zswap_current(&self) -> Option<u64> {
+		self.read_optional_value("memory.zswap.current")
+	}
+
+	/// Returns the limit on the zswap pool of this [`CGroup`], if zswap is available.
+	pub fn memory_zswap_max(&self) -> Option<Limit> {
+		self.read_optional_value("memory.zswap.max")
+	}
+
+	/// Returns the partition state of this [`CGroup`] from `cpuset.cpus.partition`.
+	pub fn cpuset_partition(&self) -> CpusetPartition {
+		self.read_value("cpuset.cpus.partition")
+	}
+
+	/// Makes this [`CGroup`] a member of its parent's CPU partition, or the root of a new one.
+	///
+	/// A partition root needs CPUs of its own, so `cpuset.cpus` must be set first and must not overlap with sibling
+	/// partitions. Newer kernels accept an invalid configuration and report it only when the state is read back, so
+	/// the state is checked after writing.
+	pub fn set_cpuset_partition(&self, partition: &CpusetPartition) {
+		if partition.is_invalid() {
+			panic!("Error: Cannot set cpuset.cpus.partition of control group {self} to \"{partition}\"; expected member, root, or isolated");
+		}
+		if *partition != CpusetPartition::Member && self.read_interface_file("cpuset.cpus").trim().is_empty() {
+			panic!("Error: Cannot make control group {self} a partition root because cpuset.cpus is empty. Set cpuset.cpus to the CPUs of the partition first.");
+		}
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		// Written directly so that the kernel's rejection is reported with the partition rules
+		let value = partition.to_string();
+		if self.skip_write(&path.join("cpuset.cpus.partition"), &value) {
+			return;
+		}
+		let result = {
+			let (path, value) = (path.join("cpuset.cpus.partition"), value.clone());
+			internal::with_io_timeout(move || fs::write(path, value))
+		};
+		audit::record(self, "set", Some("cpuset.cpus.partition"), Some(&value), &result);
+		match result {
+			Ok(()) => (),
+			Err(e) if e.raw_os_error() == Some(libc::EINVAL) => {
+				panic!("Error: The kernel rejected partition \"{partition}\" for control group {self}. The parent must be a partition root, and cpuset.cpus must be exclusive among siblings: {e}");
+			}
+			Err(e) => CGroupError::from_io(self, "cpuset.cpus.partition", e).raise(),
+		}
+		match self.cpuset_partition() {
+			actual if actual.is_invalid() => {
+				panic!("Error: The kernel reports partition of control group {self} as \"{actual}\"");
+			}
+			_ => println!("Notice: Restriction cpuset.cpus.partition=\"{partition}\" set in control group {self}"),
+		}
+	}
+
+	/// Reads the utilization clamps of this [`CGroup`] from `cpu.uclamp.min` and `cpu.uclamp.max`.
+	pub fn cpu_uclamp(&self) -> (Percentage, Percentage) {
+		self.check_uclamp();
+		(self.read_value("cpu.uclamp.min"), self.read_value("cpu.uclamp.max"))
+	}
+
+	/// Sets the utilization clamps of this [`CGroup`], which bias the CPU frequency and task placement chosen by the
+	/// scheduler: `min` requests at least that share of CPU performance, and `max` caps it.
+	///
+	/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#cpu-interface-files>
+	pub fn set_cpu_uclamp(&self, min: Percentage, max: Percentage) {
+		self.check_uclamp();
+		self.set_restriction("cpu.uclamp.min", &min.to_string());
+		self.set_restriction("cpu.uclamp.max", &max.to_string());
+	}
+
+	/// Exits with an explanation if the cpu controller is enabled but the kernel was built without utilization
+	/// clamping for control groups.
+	fn check_uclamp(&self) {
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		if self.is_controller_enabled("cpu") && !path.join("cpu.uclamp.min").exists() {
+			panic!("Error: cpu.uclamp.min is unavailable for control group {self}. Utilization clamping requires a kernel built with CONFIG_UCLAMP_TASK_GROUP.");
+		}
+	}
+
+	/// Asks the kernel to proactively reclaim the given number of bytes from this [`CGroup`] through
+	/// `memory.reclaim`, without changing its limits. Returns an estimate of the bytes reclaimed, from the drop in
+	/// `memory.current`.
+	///
+	/// The kernel may reclaim less than requested, for example if the remaining memory is in use or cannot be
+	/// swapped. The estimate also includes memory freed or allocated by the group's processes in the meantime.
+	pub fn memory_reclaim(&self, bytes: u64) -> u64 {
+		let Some(path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		if self.skip_write(&path.join("memory.reclaim"), &bytes.to_string()) {
+			return 0;
+		}
+		let before = self.memory_current();
+		let reclaim_path = path.join("memory.reclaim");
+		let mut f = match internal::with_io_timeout(move || File::options().write(true).open(reclaim_path)) {
+			Ok(f) => f,
+			Err(e) if e.kind() == io::ErrorKind::NotFound && self.is_controller_enabled("memory") => {
+				panic!("Error: memory.reclaim is unavailable for control group {self}. Proactive reclaim requires Linux 5.19 or newer.");
+			}
+			Err(e) => self.file_error("memory.reclaim", e).raise(),
+		};
+		// The write blocks while the kernel reclaims, so the timeout also bounds how long that may take
+		let result = internal::with_io_timeout(move || write!(&mut f, "{bytes}"));
+		audit::record(
+			self,
+			"reclaim",
+			Some("memory.reclaim"),
+			Some(&bytes.to_string()),
+			&result,
+		);
+		match result {
+			Ok(()) => (),
+			// The kernel gave up before reclaiming the full amount
+			Err(e) if e.raw_os_error() == Some(libc::EAGAIN) => (),
+			Err(e) => CGroupError::from_io(self, "memory.reclaim", e).raise(),
+		}
+		before.saturating_sub(self.memory_current())
+	}
+
+	/// Reads the memory limits and protections of this [`CGroup`].
+	pub fn get_memory_limits(&self) -> MemoryLimits {
+		MemoryLimits {
+			min: self.read_value("memory.min"),
+			low: self.read_value("memory.low"),
+			high: self.read_value("memory.high"),
+			max: self.read_value("memory.max"),
+		}
+	}
+
+	/// Sets the memory limits and protections of this [`CGroup`].
+	pub fn set_memory_limits(&self, limits: &MemoryLimits) {
+		self.set_restrictions(&[
+			("memory.min".to_string(), limits.min.to_string()),
+			("memory.low".to_string(), limits.low.to_string()),
+			("memory.high".to_string(), limits.high.to_string()),
+			("memory.max".to_string(), limits.max.to_string()),
+		]);
+	}
+
+	/// Returns the hard memory limit of this [`CGroup`] from `memory.max`.
+	pub fn memory_max(&self) -> Limit {
+		self.read_value("memory.max")
+	}
+
+	/// Sets the hard memory limit of this [`CGroup`] in `memory.max`. [`Limit::Max`] removes the limit.
+	///
+	/// A limit of 0 is accepted, since it is valid for the kernel, but prints a warning: the group cannot allocate
+	/// any memory, so its processes are killed by the OOM killer as soon as they try.
+	pub fn set_memory_max(&self, max: Limit) {
+		self.set_restriction("memory.max", &max.to_string());
+	}
+
+	/// Reads the breakdown of the memory usage of this [`CGroup`] from `memory.stat`.
+	pub fn memory_stat(&self) -> MemoryStat {
+		MemoryStat::from_stat(self.read_events("memory.stat"))
+	}
+
+	/// Reads a summary of the memory usage of this [`CGroup`].
+	pub fn memory_stats(&self) -> MemoryStats {
+		MemoryStats {
+			current: self.memory_current(),
+			peak: self.read_optional_value("memory.peak"),
+			swap_current: self.read_optional_value("memory.swap.current"),
+			swap_peak: self.memory_swap_peak(),
+			zswap_current: self.memory_zswap_current(),
+			zswap_max: self.memory_zswap_max(),
+		}
+	}
+
+	/// Reads the memory usage of this [`CGroup`] per NUMA node from `memory.numa_stat`.
+	pub fn memory_numa_stat(&self) -> MemoryNumaStat {
+		match MemoryNumaStat::parse(&self.read_interface_file("memory.numa_stat")) {
+			Ok(stat) => stat,
+			Err(e) => panic!("Error: Unexpected format in memory.numa_stat of control group {self}: {e}"),
+		}
+	}
+
+	/// Reads the throttling limits of this [`CGroup`] from `io.max`, keyed by device number ("MAJ:MIN").
+	///
+	/// Only devices with limits are listed, so the map is empty if no limits are set.
+	pub fn io_max(&self) -> HashMap<String, IoLimits> {
+		match IoLimits::parse(&self.read_interface_file("io.max")) {
+			Ok(devices) => devices,
+			Err(e) => panic!("Error: Unexpected format in io.max of control group {self}: {e}"),
+		}
+	}
+
+	/// Sets the throttling limits of this [`CGroup`] for one device, given by its device number ("MAJ:MIN").
+	pub fn set_io_max(&self, device: &str, limits: &IoLimits) {
+		self.set_restriction("io.max", &format!("{device} {limits}"));
+	}
+
+	/// Sets the throttling limits of this [`CGroup`] for one block device, such as one from
+	/// [`DeviceNumber::from_path`].
+	///
+	/// All four limits are written, so limits left at [`Limit::Max`] are removed.
+	pub fn set_io_limit(&self, device: DeviceNumber, limits: IoLimits) {
+		self.set_io_max(&device.to_string(), &limits);
+	}
+
+	/// Reads the event counters in `memory.events` for this [`CGroup`].
+	///
+	/// Compare two readings with [`MemoryEvents::throttled_since`] to detect throttling by `memory.high`.
+	pub fn memory_events(&self) -> MemoryEvents {
+		MemoryEvents::from_events(&self.read_events("memory.events"))
+	}
+
+	/// Allow children of the current [`CGroup`] to set restrictions on the given controllers.
+	///
+	/// Returns [`SetOutcome::Unchanged`] if the controller was already enabled for children.
+	pub fn enable_subtree_control(&self, controller: &str) -> SetOutcome {
+		self.enable_controller(controller);
+		self.add_subtree_control(controller)
+	}
+
+	/// Writes "+CONTROLLER" to `cgroup.subtree_control` of this [`CGroup`], assuming that the controller is already
+	/// available in it.
+	fn add_subtree_control(&self, controller: &str) -> SetOutcome {
+		// A group that a dry run would have created has no controllers enabled for children yet
+		let missing = self.dry_run && self.cgroupfs_path_if_exists().is_none();
+		if !missing && self.is_controller_in_subtree(controller) {
+			return SetOutcome::Unchanged;
+		}
+		// The root is exempt from the rule that only groups without processes may delegate controllers
+		if !missing && !self.is_root() && self.has_processes() {
+			println!("Warning: Control group {self} owns one or more processes. Enabling controllers in children of nonempty control groups can cause unexpected behavior. For example, a domain cgroup might turned into a threaded domain. See <https://docs.kernel.org/admin-guide/cgroup-v2.html>")
+		}
+		// It seems that this needs to be written as one chunk
+		let str_to_write = format!("+{controller}");
+		if self.skip_write(&self.cgroupfs_path().join("cgroup.subtree_control"), &str_to_write) {
+			return SetOutcome::Changed;
+		}
+		let Some(mut path) = self.cgroupfs_path_if_exists() else {
+			CGroupError::not_found(self).raise();
+		};
+		path.push("cgroup.subtree_control");
+		let mut f = match internal::with_io_timeout(move || File::options().append(true).open(path)) {
+			Ok(f) => f,
+			Err(e) => CGroupError::from_io(self, "cgroup.subtree_control", e).raise(),
+		};
+		let result = {
+			let contents = str_to_write.clone();
+			internal::with_io_timeout(move || write!(&mut f, "{contents}"))
+		};
+		audit::record(
+			self,
+			"enable",
+			Some("cgroup.subtree_control"),
+			Some(&str_to_write),
+			&result,
+		);
+		match result {
+			Ok(()) => SetOutcome::Changed,
+			Err(e) => CGroupError::from_io(self, "cgroup.subtree_control", e).raise(),
+		}
+	}
+
+	/// Stops allowing children of the current [`CGroup`] to set restrictions on the given controllers, by writing
+	/// "-CONTROLLER" to `cgroup.subtree_control`.
+	///
+	/// Returns each controller with [`SetOutcome::Unchanged`] if it was already disabled for children. The kernel
+	/// refuses to disable a controller that a child still enables for its own children, so that has to be disabled
+	/// first, from the bottom up.
+	pub fn disable_subtree_control(&self, controllers: &[&str]) -> Vec<(String, SetOutcome)> {
+		let enabled = self.subtree_controllers();
+		let mut outcomes = Vec::new();
+		for controller in controllers {
+			if !enabled.iter().any(|c| c == controller) {
+				outcomes.push((controller.to_string(), SetOutcome::Unchanged));
+				continue;
+			}
+			let path = self.cgroupfs_path().join("cgroup.subtree_control");
+			let str_to_write = format!("-{controller}");
+			if !self.skip_write(&path, &str_to_write) {
+				let result = {
+					let contents = str_to_write.clone();
+					internal::with_io_timeout(move || {
+						File::options()
+							.append(true)
+							.open(path)
+							.and_then(|mut f| write!(&mut f, "{contents}"))
+					})
+				};
+				audit::record(
+					self,
+					"disable",
+					Some("cgroup.subtree_control"),
+					Some(&str_to_write),
+					&result,
+				);
+				match result {
+					Ok(()) => (),
+					Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+						let user = self
+							.children()
+							.into_iter()
+							.find(|child| child.is_controller_in_subtree(controller))
+							.map_or_else(|| "a child".to_string(), |child| child.to_string());
+						panic!("Error: Cannot disable controller \"{controller}\" for children of {self} because it is still in use by {user}. Disable it for the children of {user} first.");
+					}
+					Err(e) => CGroupError::from_io(self, "cgroup.subtree_control", e).raise(),
+				}
+			}
+			outcomes.push((controller.to_string(), SetOutcome::Changed));
+		}
+		outcomes
+	}
+
+	/// Allow the current [`CGroup`] to set restrictions on the given controllers.
+	///
+	/// If the controller is not available yet, it is enabled in the subtree control of the parent, recursively. The
+	/// controllers of the root control group come from the kernel rather than from a parent, so they cannot be
+	/// enabled this way.
+	///
+	/// Returns [`SetOutcome::Unchanged`] if the controller was already available.
+	pub fn enable_controller(&self, controller: &str) -> SetOutcome {
+		let current_controllers = match self.parent() {
+			// A group that a dry run would have created gets the controllers enabled for children of its parent
+			Some(parent) if self.dry_run && self.cgroupfs_path_if_exists().is_none() => {
+				if parent.cgroupfs_path_if_exists().is_some() {
+					parent.subtree_controllers()
+				} else {
+					Vec::new()
+				}
+			}
+			_ => self.controllers(),
+		};
+		if current_controllers.iter().any(|c| c == controller) {
+			return SetOutcome::Unchanged;
+		}
+		let Some(parent) = self.parent() else {
+			panic!("Error: Controller \"{controller}\" is not available in this kernel. The root control group only has: {}. The controller may not be compiled into the kernel, may be disabled on the kernel command line, or may be attached to a cgroups v1 hierarchy.", current_controllers.join(" "));
+		};
+		parent.enable_subtree_control(controller);
+		SetOutcome::Changed
+	}
+
+	/// Allow the current [`CGroup`] to set the given restriction.
+	///
+	/// The controller is the part of the key before the first dot, such as "cpu" for "cpu.max". Core interface files
+	/// such as "cgroup.max.depth" need no controller, so nothing is enabled for them.
+	pub fn enable_controller_for_restriction(&self, key: &str) -> SetOutcome {
+		match controller_for_key(key) {
+			Some(controller) => self.enable_controller(controller),
+			None => SetOutcome::Unchanged,
+		}
+	}
+
+	/// Returns the controllers that the given restrictions need but that are not listed in `cgroup.controllers` of the
+	/// current [`CGroup`], in the order that they are first needed by the keys.
+	///
+	/// Writing such a restriction fails because its file does not exist, so this allows callers to report the missing
+	/// controller instead. Keys for core interface files need no controller and are never reported.
+	pub fn missing_controllers_for_restrictions(&self, keys: &[impl AsRef<str>]) -> Vec<String> {
+		let available = self.controllers();
+		let mut missing: Vec<String> = Vec::new();
+		for key in keys {
+			let Some(controller) = controller_for_key(key.as_ref()) else {
+				continue;
+			};
+			if !available.iter().any(|c| c == controller) && !missing.iter().any(|c| c == controller) {
+				missing.push(controller.to_string());
+			}
+		}
+		missing
+	}
+
+	/// Allow the current [`CGroup`] to set all of the given restrictions, enabling each required controller once.
+	///
+	/// Returns the controllers in the order that they are first needed by the keys, together with whether each one
+	/// had to be enabled. Keys for core interface files need no controller and are not included.
+	pub fn enable_controllers_for_restrictions(&self, keys: &[impl AsRef<str>]) -> Vec<(String, SetOutcome)> {
+		let mut outcomes: Vec<(String, SetOutcome)> = Vec::new();
+		for key in keys {
+			let Some(controller) = controller_for_key(key.as_ref()) else {
+				continue;
+			};
+			if outcomes.iter().any(|(c, _)| c == controller) {
+				continue;
+			}
+			outcomes.push((controller.to_string(), self.enable_controller(controller)));
+		}
+		outcomes
+	}
+
+	/// Returns the limit that this [`CGroup`] is effectively subject to for a key such as "memory.max" or "pids.max",
+	/// together with the control group that imposes it.
+	///
+	/// A control group cannot exceed the hard limits of its ancestors, so this is the lowest value of the key across
+	/// this group and all of its ancestors. If several groups share the lowest value, the one closest to this group
+	/// is returned. Groups without the interface file, such as the root, are skipped. If no group sets a limit,
+	/// returns this group and [`Limit::Max`].
+	pub fn effective_limit(&self, key: &str) -> (CGroup, Limit) {
+		let mut effective = (self.clone(), Limit::Max);
+		for cgroup in std::iter::once(self.clone()).chain(self.ancestors()) {
+			let value = match cgroup.get_restriction(key) {
+				Ok(value) => value,
+				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+				Err(e) => cgroup.file_error(key, e).raise(),
+			};
+			let limit = match value.parse::<Limit>() {
+				Ok(limit) => limit,
+				Err(e) => panic!("Error: Unexpected format in {key} of control group {cgroup}: {e}"),
+			};
+			if limit < effective.1 {
+				effective = (cgroup, limit);
+			}
+		}
+		effective
+	}
+
+	/// Reads the current value of a restriction, such as "cpu.max", with surrounding whitespace removed.
+	pub fn get_restriction(&self, key: &str) -> io::Result<String> {
+		let path = self.cgroupfs_path().join(key);
+		let contents = internal::with_io_timeout(move || fs::read_to_string(path))?;
+		Ok(contents.trim().to_string())
+	}
+
+	/// Reads a restriction like [`CGroup::get_restriction`], panicking with the same errors as
+	/// [`CGroup::set_restriction`] if the file cannot be read, such as when its controller is not enabled.
+	pub fn read_restriction(&self, key: &str) -> String {
+		if self.cgroupfs_path_if_exists().is_none() {
+			CGroupError::not_found(self).raise();
+		}
+		match self.get_restriction(key) {
+			Ok(value) => value,
+			Err(e) => self.file_error(key, e).raise(),
+		}
+	}
+
+	/// Sets a restriction based on the key (file name, like "cpu.max") and value (like "90000 100000").
+	///
+	/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html>
+	pub fn set_restriction(&self, key: &str, value: &str) {
+		if let Err(e) = self.try_set_restriction(key, value) {
+			e.raise();
+		}
+	}
+
+	/// Sets a restriction like [`CGroup::set_restriction`], enabling its controller first if the file is missing
+	/// because the controller is not enabled, like `cg2util restrict --auto` does.
+	///
+	/// The controller is enabled at most once. If the file is still missing afterwards, the kernel does not provide
+	/// it for this group, and this panics with an error saying so.
+	pub fn set_restriction_auto(&self, key: &str, value: &str) {
+		let e = match self.try_set_restriction(key, value) {
+			Ok(()) => return,
+			Err(e @ (CGroupError::ControllerNotEnabled { .. } | CGroupError::Unavailable { .. })) => e,
+			Err(e) => e.raise(),
+		};
+		let Some(controller) = controller_for_key(key) else {
+			e.raise();
+		};
+		if self.enable_controller(controller) == SetOutcome::Unchanged {
+			e.raise();
+		}
+		match self.try_set_restriction(key, value) {
+			Ok(()) => (),
+			Err(CGroupError::Unavailable { .. } | CGroupError::ControllerNotEnabled { .. }) => panic!("Error: {key} is unavailable for control group {self} even after enabling the \"{controller}\" controller. The kernel may not support this file."),
+			Err(e) => e.raise(),
+		}
+	}
+
+	/// Sets a restriction like [`CGroup::set_restriction`], but returns an error instead of panicking.
+	fn try_set_restriction(&self, key: &str, value: &str) -> Result<(), CGroupError> {
+		if key == "memory.max" && value.trim() == "0" {
+			println!("Warning: Setting memory.max=0 in control group {self} leaves no memory for its processes, so the OOM killer will kill them as soon as they allocate memory. Use \"max\" to remove the limit.");
+		}
+		if self.skip_write(&self.cgroupfs_path().join(key), value) {
+			return Ok(());
+		}
+		let Some(mut path) = self.cgroupfs_path_if_exists() else {
+			return Err(CGroupError::not_found(self));
+		};
+		path.push(key);
+		// Truncate like a shell redirect would, so that a shorter value fully replaces the previous one
+		let contents = value.to_string();
+		let result = internal::with_io_timeout(move || {
+			File::options()
+				.write(true)
+				.truncate(true)
+				.open(&path)
+				.and_then(|mut f| write!(&mut f, "{}", contents))
+		});
+		audit::record(self, "set", Some(key), Some(value), &result);
+		match result {
+			Ok(()) => {
+				println!("Notice: Restriction {key}=\"{value}\" set in control group {self}");
+				Ok(())
+			}
+			Err(e) => Err(CGroupError::rejected(self, key, value, &e).unwrap_or_else(|| self.file_error(key, e))),
+		}
+	}
+
+	/// Sets a restriction from a typed value, such as a [`Limit`] or a [`Duration`], formatted for the file. See
+	/// [`RestrictionValue`] for the accepted types.
+	///
+	/// ```no_run
+	/// use cg2tools::{CGroup, Limit};
+	///
+	/// let cgroup = CGroup::from_cgroup_path("/job");
+	/// cgroup.set("memory.max", 512 << 20);
+	/// cgroup.set("pids.max", Limit::Max);
+	/// cgroup.set("cpu.weight", "150");
+	/// ```
+	pub fn set(&self, key: &str, value: impl Into<RestrictionValue>) {
+		match value.into().format_for(key) {
+			Ok(value) => self.set_restriction(key, &value),
+			Err(e) => panic!("Error: Cannot set {key} in control group {self}: {e}"),
+		}
+	}
+
+	/// Sets a restriction like [`CGroup::set_restriction`] and returns the value that the kernel reports afterwards.
+	///
+	/// Several files canonicalize what is written to them, so the result shows the effective setting: for example,
+	/// writing "50000" to "cpu.max" reads back "50000 100000".
+	pub fn set_restriction_readback(&self, key: &str, value: &str) -> String {
+		self.set_restriction(key, value);
+		match self.get_restriction(key) {
+			Ok(value) => value,
+			Err(e) => self.file_error(key, e).raise(),
+		}
+	}
+
+	/// Returns the restrictions of a [`Profile`] that differ from the current values in this [`CGroup`], as tuples of
+	/// file name, current value, and the value in the profile.
+	///
+	/// Returns an empty list if the control group does not exist yet.
+	pub fn profile_conflicts(&self, profile: &Profile) -> Vec<(String, String, String)> {
+		if self.cgroupfs_path_if_exists().is_none() {
+			return Vec::new();
+		}
+		let mut conflicts = Vec::new();
+		for (key, value) in profile.restrictions.iter() {
+			let Some(current) = self.read_optional_interface_file(key) else {
+				continue;
+			};
+			let current = current.trim();
+			if current != value {
+				conflicts.push((key.clone(), current.to_string(), value.clone()));
+			}
+		}
+		conflicts
+	}
+
+	/// Enables the controllers and sets the restrictions of a [`Profile`] in this [`CGroup`].
+	pub fn apply_profile(&self, profile: &Profile) {
+		self.enable_controllers_and_restrict(&profile.controllers, &profile.restrictions);
+	}
+
+	/// Sets up a control group that is ready to use in one call: creates it, hands it over to `owner`, enables
+	/// `controllers` and the controllers that the restrictions need, and sets `restrictions`, in that order.
+	///
+	/// The owner is given as "USER" or "USER:GROUP", like chown(1), and becomes the owner of the directory and of
+	/// the files that the kernel allows to be delegated: `cgroup.procs`, `cgroup.threads`, and
+	/// `cgroup.subtree_control`. The owner can then create child groups and move its processes between them, but
+	/// cannot change the limits of the group itself. See "Delegation" in
+	/// <https://docs.kernel.org/admin-guide/cgroup-v2.html>.
+	///
+	/// Restrictions are written in the same order as [`CGroup::set_restrictions`].
+	pub fn provision(
+		&self,
+		owner: Option<&str>,
+		controllers: &[&str],
+		restrictions: &[(String, String)],
+	) -> ProvisionReport {
+		let created = match self.create() {
+			Ok(created) => created,
+			Err(e) => panic!("Error: While creating control group {self}: {e}"),
+		};
+		let owner = owner.map(|owner| {
+			let (uid, gid) = match internal::resolve_owner(owner) {
+				Ok(ids) => ids,
+				Err(e) => panic!("Error: Invalid owner for control group {self}: {e}"),
+			};
+			let path = self.cgroupfs_path();
+			for file in std::iter::once(None).chain(DELEGATED_FILES.iter().map(Some)) {
+				let (name, path) = match file {
+					Some(file) => (*file, path.join(file)),
+					None => (".", path.clone()),
+				};
+				if self.dry_run {
+					let owner = gid.map_or(uid.to_string(), |gid| format!("{uid}:{gid}"));
+					println!("Would change the owner of {} to {owner}", path.display());
+					continue;
+				}
+				if let Err(e) = std::os::unix::fs::chown(&path, Some(uid), gid) {
+					CGroupError::from_io(self, name, e).raise();
+				}
+			}
+			(uid, gid)
+		});
+		let controllers = controllers.iter().map(ToString::to_string).collect::<Vec<_>>();
+		let controllers = self.enable_controllers_and_restrict(&controllers, restrictions);
+		ProvisionReport {
+			created,
+			owner,
+			controllers,
+			restrictions: restrictions.to_vec(),
+		}
+	}
+
+	/// Enables the given controllers and the ones that the restrictions need, then sets the restrictions. The
+	/// available controllers are read once, so only the missing ones are enabled.
+	fn enable_controllers_and_restrict(
+		&self,
+		controllers: &[String],
+		restrictions: &[(String, String)],
+	) -> Vec<(String, SetOutcome)> {
+		let available = self.controllers();
+		let needed = restrictions.iter().filter_map(|(key, _)| controller_for_key(key));
+		let mut outcomes: Vec<(String, SetOutcome)> = Vec::new();
+		for controller in controllers.iter().map(String::as_str).chain(needed) {
+			if outcomes.iter().any(|(c, _)| c == controller) {
+				continue;
+			}
+			let outcome = if available.iter().any(|c| c == controller) {
+				SetOutcome::Unchanged
+			} else {
+				self.enable_controller(controller)
+			};
+			outcomes.push((controller.to_string(), outcome));
+		}
+		self.set_restrictions(restrictions);
+		outcomes
+	}
+
+	/// Sets several restrictions, reordering them so that related files are written in a safe sequence.
+	///
+	/// Only the cpuset files are reordered: `cpuset.cpus`, `cpuset.mems`, and `cpuset.cpus.exclusive` are written
+	/// first, since they must be configured before `cpuset.cpus.partition` can make the group a partition root. All
+	/// other restrictions follow in the order given.
+	pub fn set_restrictions(&self, restrictions: &[(String, String)]) {
+		for (key, value) in sorted_restrictions(restrictions) {
+			self.set_restriction(key, value);
+		}
+	}
+
+	/// Sets several restrictions in the order given, with all-or-nothing semantics.
+	///
+	/// The current values of all files are read first. If any write fails, the restrictions that were already set are
+	/// restored to their previous values, in reverse order, and the error of the failed write is returned.
+	///
+	/// A rollback is only as good as the values that the kernel reports. Files that canonicalize what is written to
+	/// them restore an equivalent setting rather than the original text, and files whose reported value cannot be
+	/// written back, such as "cpuset.cpus.partition" after the kernel has marked the partition invalid, are left as
+	/// they are with a warning.
+	pub fn apply_with_rollback(&self, changes: &[(String, String)]) -> Result<(), CGroupError> {
+		if self.cgroupfs_path_if_exists().is_none() {
+			return Err(CGroupError::not_found(self));
+		}
+		let mut previous = Vec::with_capacity(changes.len());
+		for (key, _) in changes {
+			match self.get_restriction(key) {
+				Ok(value) => previous.push(value),
+				Err(e) => return Err(self.file_error(key, e)),
+			}
+		}
+		for (i, (key, value)) in changes.iter().enumerate() {
+			let Err(e) = self.try_set_restriction(key, value) else {
+				continue;
+			};
+			for ((key, _), value) in changes[..i].iter().zip(&previous).rev() {
+				if let Err(e) = self.try_set_restriction(key, value) {
+					println!("Warning: Could not restore {key}=\"{value}\" in control group {self}: {e}");
+				}
+			}
+			return Err(e);
+		}
+		Ok(())
+	}
+
+	/// Sets a restriction only if its current value is different, and reports whether it was written.
+	///
+	/// Values are compared as text after trimming whitespace. Some files canonicalize what is written to them (for
+	/// example, "cpu.max" reads back "50000 100000" after writing "50000"), so a value in a different but
+	/// equivalent form is written again and reported as [`SetOutcome::Changed`].
+	pub fn set_restriction_if_changed(&self, key: &str, value: &str) -> SetOutcome {
+		if let Ok(current) = self.get_restriction(key) {
+			if current == value.trim() {
+				return SetOutcome::Unchanged;
+			}
+		}
+		self.set_restriction(key, value);
+		SetOutcome::Changed
+	}
+
+	/// Like [`CGroup::set_restrictions`], but skips restrictions that already have the requested value. Returns the
+	/// number of restrictions that were written.
+	pub fn set_restrictions_if_changed(&self, restrictions: &[(String, String)]) -> usize {
+		sorted_restrictions(restrictions)
+			.into_iter()
+			.filter(|(key, value)| self.set_restriction_if_changed(key, value) == SetOutcome::Changed)
+			.count()
+	}
+
+	/// Resets a restriction to the kernel's default for a new control group, such as "max" for "memory.max".
+	///
+	/// Returns false without writing anything if the file has no known default.
+	pub fn reset_restriction(&self, key: &str) -> bool {
+		let Some((_, default)) = RESTRICTION_DEFAULTS.iter().find(|(k, _)| *k == key) else {
+			return false;
+		};
+		self.set_restriction(key, default);
+		true
+	}
+
+	/// Resets every restriction of this [`CGroup`] that has a known default, returning the names of the other
+	/// writable restriction files, which are left alone.
+	///
+	/// Files that trigger actions rather than hold settings, such as `cgroup.procs` and `cgroup.kill`, are ignored.
+	pub fn reset_restrictions(&self) -> Vec<String> {
+		let mut skipped = Vec::new();
+		let files = self.interface_files();
+		// Visit in the order of the defaults table so that, for example, a partition is dissolved first
+		for (key, _) in RESTRICTION_DEFAULTS {
+			if files.iter().any(|file| file == key) {
+				self.reset_restriction(key);
+			}
+		}
+		for file in files {
+			if RESTRICTION_DEFAULTS.iter().any(|(key, _)| *key == file) || is_action_file(&file) {
+				continue;
+			}
+			if self.is_writable(&file) {
+				skipped.push(file);
+			}
+		}
+		skipped
+	}
+}
+
+/// Joins items as in a sentence: "a", "a and b", or "a, b, and c".
+fn join_prose(items: &[String]) -> String {
+	match items {
+		[] => String::new(),
+		[item] => item.clone(),
+		[first, second] => format!("{first} and {second}"),
+		[rest @ .., last] => format!("{}, and {last}", rest.join(", ")),
+	}
+}
+
+/// Defaults of the restrictions of a new control group, in the order in which they are reset.
+const RESTRICTION_DEFAULTS: &[(&str, &str)] = &[
+	("cpuset.cpus.partition", "member"),
+	("cgroup.max.descendants", "max"),
+	("cgroup.max.depth", "max"),
+	("cpu.weight", "100"),
+	("cpu.max", "max"),
+	("cpu.max.burst", "0"),
+	("cpu.idle", "0"),
+	("memory.min", "0"),
+	("memory.low", "0"),
+	("memory.high", "max"),
+	("memory.max", "max"),
+	("memory.swap.high", "max"),
+	("memory.swap.max", "max"),
+	("memory.zswap.max", "max"),
+	("memory.oom.group", "0"),
+	("io.weight", "default 100"),
+	("pids.max", "max"),
+];
+
+/// Returns whether a writable interface file triggers an action or moves processes rather than holding a setting.
+fn is_action_file(file: &str) -> bool {
+	const ACTION_FILES: &[&str] = &[
+		"cgroup.procs",
+		"cgroup.threads",
+		"cgroup.subtree_control",
+		"cgroup.type",
+		"cgroup.freeze",
+		"cgroup.kill",
+		"memory.reclaim",
+		"memory.peak",
+		"memory.swap.peak",
+	];
+	// Pressure files are writable to register triggers
+	ACTION_FILES.contains(&file) || file.ends_with(".pressure")
+}
+
+/// Whether [`CGroup::set_restriction_if_changed`] wrote to the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOutcome {
+	/// The value was different and has been written.
+	Changed,
+	/// The file already had the requested value.
+	Unchanged,
+}
+
+/// What [`CGroup::provision`] did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisionReport {
+	/// Whether the control group was created, rather than already existing.
+	pub created: bool,
+	/// The user ID and group ID that the delegated files were given to, if an owner was requested. The group is
+	/// `None` if it was left unchanged.
+	pub owner: Option<(u32, Option<u32>)>,
+	/// Each controller that was requested or needed by a restriction, with whether it had to be enabled.
+	pub controllers: Vec<(String, SetOutcome)>,
+	/// The restrictions that were set.
+	pub restrictions: Vec<(String, String)>,
+}
+
+/// Where a controller can be used, as returned by [`CGroup::list_controllers_with_availability`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerStatus {
+	/// Name of the controller, such as "cpu".
+	pub name: String,
+	/// Whether the kernel provides the controller, from `cgroup.controllers` of the root.
+	pub available_at_root: bool,
+	/// Whether the control group may use the controller, from its `cgroup.controllers`.
+	pub available: bool,
+	/// Whether children of the control group may use the controller, from its `cgroup.subtree_control`.
+	pub in_subtree: bool,
+}
+
+/// The type of a [`CGroup`], from `cgroup.type`.
+///
+/// See "Threads" in <https://docs.kernel.org/admin-guide/cgroup-v2.html> for the rules of each type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CGroupType {
+	/// A normal group, whose processes are listed in `cgroup.procs`.
+	Domain,
+	/// The root of a threaded subtree. It can hold processes, whose threads may be spread over the subtree.
+	DomainThreaded,
+	/// A group in an invalid state that can hold no processes until its type is changed.
+	DomainInvalid,
+	/// A member of a threaded subtree. Its threads are listed in `cgroup.threads`.
+	Threaded,
+}
+
+impl fmt::Display for CGroupType {
+	/// Formats the type as it appears in `cgroup.type`, such as "domain threaded".
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Domain => "domain",
+			Self::DomainThreaded => "domain threaded",
+			Self::DomainInvalid => "domain invalid",
+			Self::Threaded => "threaded",
+		})
+	}
+}
+
+impl CGroupType {
+	fn parse(contents: &str) -> Option<Self> {
+		match contents.trim() {
+			"domain" => Some(Self::Domain),
+			"domain threaded" => Some(Self::DomainThreaded),
+			"domain invalid" => Some(Self::DomainInvalid),
+			"threaded" => Some(Self::Threaded),
+			_ => None,
+		}
+	}
+}
+
+/// Parses a list of process or thread IDs, one per line, from the interface file `name`.
+fn parse_ids(name: &'static str, contents: &str) -> Result<Vec<u32>, (&'static str, io::Error)> {
+	contents
+		.lines()
+		.map(|line| {
+			line.trim()
+				.parse()
+				.map_err(|e| (name, io::Error::new(io::ErrorKind::InvalidData, format!("line {line:?}: {e}"))))
+		})
+		.collect()
+}
+
+/// Returns the process that owns a thread, from the "Tgid" field of `/proc/<tid>/status`.
+fn thread_group_id(proc_root: &Path, tid: u32) -> Option<u32> {
+	let contents = fs::read_to_string(proc_root.join(tid.to_string()).join("status")).ok()?;
+	contents
+		.lines()
+		.find_map(|line| line.strip_prefix("Tgid:")?.trim().parse().ok())
+}
+
+/// Returns the controller that provides an interface file, or `None` for the core "cgroup." files.
+pub(crate) fn controller_for_key(key: &str) -> Option<&str> {
+	match key.split_once('.') {
+		Some(("cgroup", _)) | None => None,
+		Some((controller, _)) => Some(controller),
+	}
+}
+
+/// Interface files that are handed over to the owner of a delegated control group, besides its directory.
+const DELEGATED_FILES: &[&str] = &["cgroup.procs", "cgroup.threads", "cgroup.subtree_control"];
+
+/// Controllers of the unified hierarchy that are listed by [`CGroup::list_controllers_with_availability`] even if the
+/// kernel does not provide them.
+const KNOWN_CONTROLLERS: &[&str] = &["cpuset", "cpu", "io", "memory", "hugetlb", "pids", "rdma", "misc"];
+
+/// Interface files that must be written in this order when several are set together: the cpuset CPUs and memory
+/// nodes must be configured before the group can become a partition root.
+///
+/// The memory limits and protections need no order, since the kernel does not check them against each other, and
+/// `cpu.max` takes its quota and period in a single write.
+const RESTRICTION_ORDER: &[&str] = &[
+	"cpuset.cpus",
+	"cpuset.mems",
+	"cpuset.cpus.exclusive",
+	"cpuset.cpus.partition",
+];
+
+/// Returns the restrictions in the order they should be written. See [`RESTRICTION_ORDER`].
+fn sorted_restrictions(restrictions: &[(String, String)]) -> Vec<&(String, String)> {
+	let mut sorted = restrictions.iter().collect::<Vec<_>>();
+	sorted.sort_by_key(|(key, _)| {
+		RESTRICTION_ORDER
+			.iter()
+			.position(|k| k == key)
+			.unwrap_or(RESTRICTION_ORDER.len())
+	});
+	sorted
+}
+
+/// Number of times an operation is retried after failing with EBUSY.
+const BUSY_RETRIES: u32 = 5;
+
+/// Delay before the first retry after EBUSY; it doubles after every further attempt.
+const BUSY_INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Runs `f`, retrying with exponential backoff while it fails with EBUSY.
+///
+/// The kernel returns EBUSY for transient conditions, such as when a process is moved while the destination's
+/// type is changing or a controller is being enabled concurrently. `on_retry` is called with the delay before
+/// each retry. After [`BUSY_RETRIES`] retries, the last error is returned.
+fn retry_on_busy<T>(mut f: impl FnMut() -> io::Result<T>, mut on_retry: impl FnMut(Duration)) -> io::Result<T> {
+	let mut delay = BUSY_INITIAL_BACKOFF;
+	for _ in 0..BUSY_RETRIES {
+		match f() {
+			Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+				on_retry(delay);
+				thread::sleep(delay);
+				delay *= 2;
+			}
+			result => return result,
+		}
+	}
+	f()
+}
+
+/// Writes a process ID to an open `cgroup.procs` file.
+///
+/// The kernel moves one process per write(2), so the PID is formatted up front and written as a single chunk.
+fn write_pid(f: &mut impl Write, pid: u32) -> io::Result<()> {
+	f.write_all(pid.to_string().as_bytes())
+}
+
+/// Unit types that can own a control group.
+const UNIT_SUFFIXES: &[&str] = &[".service", ".scope", ".slice", ".socket", ".mount", ".swap"];
+
+/// Returns the mount point of the first cgroup2 file system in the contents of `/proc/self/mountinfo`.
+///
+/// Each line holds the mount ID, parent ID, device, root and mount point, then optional fields ended by "-", then the
+/// file system type. Spaces and other special characters in the mount point are escaped as octal, such as "\040".
+fn cgroup2_mount_point(mountinfo: &str) -> Option<PathBuf> {
+	mountinfo.lines().find_map(|line| {
+		let (fields, rest) = line.split_once(" - ")?;
+		if rest.split(' ').next()? != "cgroup2" {
+			return None;
+		}
+		let mount_point = fields.split(' ').nth(4)?;
+		Some(PathBuf::from(OsString::from_vec(unescape_octal(mount_point))))
+	})
+}
+
+/// Decodes the octal escapes, such as "\040" for a space, that the kernel uses for paths in `/proc/self/mountinfo`.
+fn unescape_octal(input: &str) -> Vec<u8> {
+	let bytes = input.as_bytes();
+	let mut output = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		let escape = bytes
+			.get(i + 1..i + 4)
+			.filter(|digits| bytes[i] == b'\\' && digits.iter().all(|d| matches!(d, b'0'..=b'7')));
+		match escape {
+			Some(digits) => {
+				output.push(
+					digits
+						.iter()
+						.fold(0u8, |value, d| value.wrapping_mul(8).wrapping_add(d - b'0')),
+				);
+				i += 4;
+			}
+			None => {
+				output.push(bytes[i]);
+				i += 1;
+			}
+		}
+	}
+	output
+}
+
+/// Checks that a systemd unit name has a known type and only the characters that systemd allows.
+fn validate_unit_name(unit: &str) -> Result<(), String> {
+	let Some(suffix) = UNIT_SUFFIXES.iter().find(|suffix| unit.ends_with(*suffix)) else {
+		return Err(format!(
+			"unit \"{unit}\" must end with one of: {}",
+			UNIT_SUFFIXES.join(" ")
+		));
+	};
+	let prefix = &unit[..unit.len() - suffix.len()];
+	if prefix.is_empty() || unit.len() > 255 {
+		return Err(format!("unit \"{unit}\" has an invalid length"));
+	}
+	if !prefix
+		.chars()
+		.all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '-' | '_' | '.' | '\\' | '@'))
+	{
+		return Err(format!("unit \"{unit}\" contains invalid characters"));
+	}
+	Ok(())
+}
+
+/// Asks systemd for the control group of a unit, returning `None` if systemd is unavailable or has no control
+/// group for the unit.
+fn systemctl_control_group(unit: &str) -> Option<String> {
+	let output = process::Command::new("systemctl")
+		.args(["show", "--property=ControlGroup", "--value", "--", unit])
+		.stderr(process::Stdio::null())
+		.output()
+		.ok()?;
+	let path = String::from_utf8(output.stdout).ok()?.trim().to_string();
+	(output.status.success() && path.starts_with('/')).then_some(path)
+}
+
+/// Returns the path where systemd places a unit by default.
+fn conventional_unit_path(unit: &str) -> String {
+	let Some(name) = unit.strip_suffix(".slice") else {
+		return format!("/system.slice/{unit}");
+	};
+	if name == "-" {
+		return "/".to_string();
+	}
+	// Each dash-separated prefix of a slice name is a parent slice
+	let mut path = String::new();
+	for (i, _) in name.match_indices('-') {
+		path.push_str(&format!("/{}.slice", &name[..i]));
+	}
+	path.push_str(&format!("/{unit}"));
+	path
+}
+
+/// Lists the names of the subdirectories (if `dirs` is true) or the other entries (if `dirs` is false) of a
+/// directory, in sorted order.
+fn list_dir_entries(path: &Path, dirs: bool) -> io::Result<Vec<String>> {
+	let mut names = Vec::new();
+	for entry in fs::read_dir(path)? {
+		let entry = entry?;
+		if entry.file_type()?.is_dir() != dirs {
+			continue;
+		}
+		names.push(entry.file_name().to_string_lossy().into_owned());
+	}
+	names.sort();
+	Ok(names)
+}
+
+impl AsRef<Path> for CGroup {
+	fn as_ref(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl fmt::Display for CGroup {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		self.path.display().fmt(f)
+	}
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct ChunkRecorder(Vec<String>);
+
+#[cfg(test)]
+impl Write for ChunkRecorder {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.push(String::from_utf8(buf.to_vec()).unwrap());
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[test]
+fn test_write_pid() {
+	let mut recorder = ChunkRecorder::default();
+	write_pid(&mut recorder, 12345).unwrap();
+	assert_eq!(recorder.0, ["12345"]);
+
+	// Each PID must arrive in its own write(2), with no separators
+	let mut recorder = ChunkRecorder::default();
+	for pid in [123, 4567, 8] {
+		write_pid(&mut recorder, pid).unwrap();
+	}
+	assert_eq!(recorder.0, ["123", "4567", "8"]);
+}
+
+#[test]
+fn test_list_dir_entries() {
+	let dir = tempfile::tempdir().unwrap();
+	for name in ["cpu.max", "cgroup.procs", "memory.stat", "cgroup.controllers"] {
+		File::create(dir.path().join(name)).unwrap();
+	}
+	for name in ["child_b", "child_a"] {
+		fs::create_dir(dir.path().join(name)).unwrap();
+	}
+	assert_eq!(
+		list_dir_entries(dir.path(), false).unwrap(),
+		["cgroup.controllers", "cgroup.procs", "cpu.max", "memory.stat"]
+	);
+	assert_eq!(list_dir_entries(dir.path(), true).unwrap(), ["child_a", "child_b"]);
+}
+
+#[test]
+fn test_sorted_restrictions() {
+	let restrictions = [
+		("cpu.weight", "150"),
+		("memory.high", "1G"),
+		("cpuset.cpus.partition", "root"),
+		("pids.max", "100"),
+		("memory.max", "2G"),
+		("cpuset.cpus", "0-3"),
+	]
+	.map(|(k, v)| (k.to_string(), v.to_string()));
+	let keys = sorted_restrictions(&restrictions)
+		.into_iter()
+		.map(|(k, _)| k.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		keys,
+		[
+			"cpuset.cpus",
+			"cpuset.cpus.partition",
+			"cpu.weight",
+			"memory.high",
+			"pids.max",
+			"memory.max"
+		]
+	);
+}
+
+#[test]
+fn test_parse_value() {
+	let cgroup = CGroup::from_cgroup_path("/a");
+	assert_eq!(cgroup.parse_value::<u64>("memory.swap.peak", "4096\n"), 4096);
+	assert_eq!(cgroup.parse_value::<u64>("memory.zswap.current", "0\n"), 0);
+	assert_eq!(cgroup.parse_value::<Limit>("memory.zswap.max", "max\n"), Limit::Max);
+	assert_eq!(
+		cgroup.parse_value::<Limit>("memory.zswap.max", "1073741824\n"),
+		Limit::Value(1073741824)
+	);
+}
+
+#[test]
+fn test_is_descendant_of() {
+	let cgroup = CGroup::from_cgroup_path("/a/b");
+	let other = CGroup::from_cgroup_path;
+	assert!(other("/a/b").is_same_or_descendant_of(&cgroup));
+	assert!(other("/a/b/c").is_same_or_descendant_of(&cgroup));
+	assert!(!other("/a").is_same_or_descendant_of(&cgroup));
+	assert!(!other("/a/bc").is_same_or_descendant_of(&cgroup));
+	assert!(cgroup.is_same_or_descendant_of(&other("/")));
+	assert!(!other("/a/b").is_descendant_of(&cgroup));
+	assert!(other("/a/b/c").is_descendant_of(&cgroup));
+	assert!(!other("/").is_descendant_of(&other("/")));
+	// Names are normalized before comparing
+	assert!(other("/a/b/").is_same_or_descendant_of(&other("/a/./b")));
+	assert!(!other("/a/b/..").is_descendant_of(&cgroup));
+	assert!(other("/a/b/../c/d").is_descendant_of(&other("/a/c")));
+}
+
+#[test]
+fn test_retry_on_busy() {
+	// Fails twice with EBUSY, then succeeds
+	let mut attempts = 0;
+	let mut delays = Vec::new();
+	let result = retry_on_busy(
+		|| {
+			attempts += 1;
+			if attempts <= 2 {
+				Err(io::Error::from_raw_os_error(libc::EBUSY))
+			} else {
+				Ok(attempts)
+			}
+		},
+		|delay| delays.push(delay),
+	);
+	assert_eq!(result.unwrap(), 3);
+	assert_eq!(delays, [BUSY_INITIAL_BACKOFF, BUSY_INITIAL_BACKOFF * 2]);
+
+	// Other errors are not retried
+	let mut attempts = 0;
+	let result: io::Result<()> = retry_on_busy(
+		|| {
+			attempts += 1;
+			Err(io::Error::from_raw_os_error(libc::EACCES))
+		},
+		|_| panic!("should not retry"),
+	);
+	assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EACCES));
+	assert_eq!(attempts, 1);
+
+	// Gives up after the retry limit
+	let mut attempts = 0;
+	let result: io::Result<()> = retry_on_busy(
+		|| {
+			attempts += 1;
+			Err(io::Error::from_raw_os_error(libc::EBUSY))
+		},
+		|_| (),
+	);
+	assert_eq!(result.unwrap_err().raw_os_error(), Some(libc::EBUSY));
+	assert_eq!(attempts, BUSY_RETRIES + 1);
+}
+
+/// Creates a fake cgroup file system in a temporary directory with the given files, relative to its root.
+#[cfg(test)]
+fn fake_cgroupfs(files: &[(&str, &str)]) -> tempfile::TempDir {
+	let dir = tempfile::tempdir().unwrap();
+	for (name, contents) in files {
+		let path = dir.path().join(name);
+		fs::create_dir_all(path.parent().unwrap()).unwrap();
+		fs::write(path, contents).unwrap();
+	}
+	dir
+}
+
+#[test]
+fn test_memory_limits() {
+	let root = fake_cgroupfs(&[
+		("a/memory.min", "0\n"),
+		("a/memory.low", "0\n"),
+		("a/memory.high", "max\n"),
+		("a/memory.max", "max\n"),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	assert_eq!(
+		cgroup.get_memory_limits(),
+		MemoryLimits {
+			min: Limit::Value(0),
+			low: Limit::Value(0),
+			high: Limit::Max,
+			max: Limit::Max,
+		}
+	);
+	let limits = MemoryLimits {
+		min: Limit::Value(1 << 20),
+		low: Limit::Value(1 << 28),
+		high: Limit::Value(1 << 30),
+		max: Limit::Value(2 << 30),
+	};
+	cgroup.set_memory_limits(&limits);
+	assert_eq!(cgroup.get_memory_limits(), limits);
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/memory.max")).unwrap(),
+		"2147483648"
+	);
+}
+
+#[test]
+fn test_subtree_controllers() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.controllers", "cpuset cpu io memory pids\n"),
+		("a/cgroup.subtree_control", "cpu memory\n"),
+		("a/b/cgroup.subtree_control", "\n"),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	assert_eq!(cgroup.controllers(), ["cpuset", "cpu", "io", "memory", "pids"]);
+	assert_eq!(cgroup.subtree_controllers(), ["cpu", "memory"]);
+	assert!(CGroup::with_root(root.path(), "/a/b").subtree_controllers().is_empty());
+}
+
+#[test]
+fn test_set_restriction_if_changed() {
+	let root = fake_cgroupfs(&[("a/cpu.weight", "100\n"), ("a/pids.max", "max\n")]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	assert_eq!(
+		cgroup.set_restriction_if_changed("cpu.weight", "100"),
+		SetOutcome::Unchanged
+	);
+	assert_eq!(fs::read_to_string(root.path().join("a/cpu.weight")).unwrap(), "100\n");
+	assert_eq!(
+		cgroup.set_restriction_if_changed("cpu.weight", "150"),
+		SetOutcome::Changed
+	);
+	assert_eq!(cgroup.get_restriction("cpu.weight").unwrap(), "150");
+
+	let restrictions = [("cpu.weight", "150"), ("pids.max", "64")].map(|(k, v)| (k.to_string(), v.to_string()));
+	assert_eq!(cgroup.set_restrictions_if_changed(&restrictions), 1);
+	assert_eq!(cgroup.set_restrictions_if_changed(&restrictions), 0);
+}
+
+#[test]
+fn test_cgroup2_mount_point() {
+	let hybrid = "\
+25 30 0:23 / /sys rw,nosuid,nodev,noexec,relatime shared:7 - sysfs sysfs rw
+33 25 0:28 / /sys/fs/cgroup ro,nosuid,nodev,noexec shared:9 - tmpfs tmpfs ro,mode=755
+34 33 0:29 / /sys/fs/cgroup/unified rw,nosuid,nodev,noexec,relatime shared:10 - cgroup2 cgroup2 rw,nsdelegate
+35 33 0:30 / /sys/fs/cgroup/systemd rw,nosuid,nodev,noexec,relatime shared:11 - cgroup cgroup rw,xattr,name=systemd
+";
+	assert_eq!(
+		cgroup2_mount_point(hybrid),
+		Some(PathBuf::from("/sys/fs/cgroup/unified"))
+	);
+	let unified = "29 23 0:26 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:4 - cgroup2 cgroup2 rw\n";
+	assert_eq!(cgroup2_mount_point(unified), Some(PathBuf::from("/sys/fs/cgroup")));
+	// Optional fields are variable in number, and mount points escape spaces
+	let container = "612 600 0:26 /job /my\\040cgroups rw master:4 shared:8 - cgroup2 cgroup2 rw\n";
+	assert_eq!(cgroup2_mount_point(container), Some(PathBuf::from("/my cgroups")));
+	assert_eq!(cgroup2_mount_point(&hybrid.replace("cgroup2", "cgroup")), None);
+	assert_eq!(unescape_octal("a\\134b\\01"), b"a\\b\\01");
+}
+
+#[test]
+fn test_exists() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", "")]);
+	assert!(CGroup::with_root(root.path(), "/a").exists().unwrap());
+	assert!(!CGroup::with_root(root.path(), "/b").exists().unwrap());
+	assert!(!CGroup::with_root(root.path(), "/b/c").exists().unwrap());
+	let long = format!("/{}", "x".repeat(300));
+	assert!(CGroup::with_root(root.path(), long).exists().is_err());
+}
+
+#[test]
+fn test_cgroupfs_path() {
+	let cgroupfs_path = |path: &str| CGroup::with_root("/cg", path).cgroupfs_path();
+	assert_eq!(cgroupfs_path("/"), PathBuf::from("/cg"));
+	assert_eq!(cgroupfs_path("/a/b/c/d/e/f/g"), PathBuf::from("/cg/a/b/c/d/e/f/g"));
+	assert_eq!(cgroupfs_path("/a/b/../c/./d"), PathBuf::from("/cg/a/c/d"));
+	// ".." never leads out of the cgroup file system
+	assert_eq!(cgroupfs_path("/a/../../../etc"), PathBuf::from("/cg/etc"));
+	assert_eq!(cgroupfs_path("/.."), PathBuf::from("/cg"));
+
+	let mut cgroup = CGroup::with_root("/cg", "/service/main");
+	cgroup.append("../sibling");
+	assert_eq!(cgroup.cgroupfs_path(), PathBuf::from("/cg/service/sibling"));
+	cgroup.append("a/../b/./c/..");
+	assert_eq!(cgroup.cgroupfs_path(), PathBuf::from("/cg/service/sibling/b"));
+	// Relative names are resolved within the hierarchy, and ".." stops at its root
+	cgroup.append("../../../../escape");
+	assert_eq!(cgroup.cgroupfs_path(), PathBuf::from("/cg/escape"));
+	cgroup.append("/x/y/z");
+	assert_eq!(cgroup.cgroupfs_path(), PathBuf::from("/cg/x/y/z"));
+}
+
+#[test]
+fn test_root() {
+	assert!(CGroup::root().is_root());
+	assert!(CGroup::from_cgroup_path("/a/..").is_root());
+	assert!(!CGroup::from_cgroup_path("/a").is_root());
+	assert_eq!(CGroup::root().parent(), None);
+	assert_eq!(CGroup::from_cgroup_path("/a/..").parent(), None);
+	assert_eq!(CGroup::from_cgroup_path("/a/b/..").parent(), Some(CGroup::root()));
+	assert!(CGroup::root().ancestors().is_empty());
+
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", ""),
+		("cgroup.procs", "1\n"),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/");
+	// The root may enable controllers for children even though it has processes
+	assert_eq!(cgroup.enable_subtree_control("memory"), SetOutcome::Changed);
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"+memory"
+	);
+	assert_eq!(cgroup.delegation_chain("cpu"), [(cgroup.clone(), false)]);
+	let result = std::panic::catch_unwind(|| cgroup.set_restriction("memory.max", "1G"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert_eq!(
+		message,
+		"Error: memory.max is unavailable for the root control group, which has no resource limits. Set it in a child control group instead."
+	);
+}
+
+#[test]
+fn test_read_restriction() {
+	let root = fake_cgroupfs(&[
+		("cgroup.subtree_control", "cpu\n"),
+		("a/cpu.weight", "150\n"),
+		("a/cgroup.procs", ""),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.read_restriction("cpu.weight"), "150");
+	let result = std::panic::catch_unwind(|| a.read_restriction("memory.max"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert_eq!(
+		message,
+		"Error: memory.max is unavailable for control group /a because the \"memory\" controller is not enabled for children of /. Enable it there with `cg2util control / +memory`, or pass --auto."
+	);
+	let result = std::panic::catch_unwind(|| a.read_restriction("cpu.max"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert_eq!(message, "Error: cpu.max is unavailable for control group /a");
+	let result = std::panic::catch_unwind(|| CGroup::with_root(root.path(), "/b").read_restriction("cpu.weight"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert_eq!(message, "Error: Control group /b does not exist");
+}
+
+#[test]
+fn test_enable_controller_at_root() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", ""),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", ""),
+	]);
+	// Available at the root: nothing to enable
+	CGroup::with_root(root.path(), "/").enable_controller("cpu");
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		""
+	);
+	// A child of the root gets it from the root's subtree control
+	CGroup::with_root(root.path(), "/a").enable_controller("memory");
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"+memory"
+	);
+}
+
+#[test]
+fn test_create_with_subtree_control() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", "cpu memory\n"),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", "cpu memory\n"),
+		("a/cgroup.subtree_control", ""),
+		("a/cgroup.procs", ""),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a/b");
+	let mut created = Vec::new();
+	let configured = cgroup
+		.create_with_subtree_control(&["cpu", "memory"], |level| created.push(level.to_string()))
+		.unwrap();
+	assert_eq!(created, ["/a/b"]);
+	// Only the nearest existing ancestor is configured, not the root above it
+	assert_eq!(configured.len(), 1);
+	assert_eq!(configured[0].0.to_string(), "/a");
+	assert_eq!(
+		configured[0].1,
+		[
+			("cpu".to_string(), SetOutcome::Changed),
+			("memory".to_string(), SetOutcome::Changed)
+		]
+	);
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/cgroup.subtree_control")).unwrap(),
+		"+cpu+memory"
+	);
+	assert!(cgroup.exists().unwrap());
+}
+
+#[test]
+fn test_create_with_subtree_control_dry_run() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", ""),
+		("cgroup.procs", ""),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a/b/c").with_dry_run(true);
+	let mut created = Vec::new();
+	let configured = cgroup
+		.create_with_subtree_control(&["cpu"], |level| created.push(level.to_string()))
+		.unwrap();
+	assert_eq!(created, ["/a", "/a/b", "/a/b/c"]);
+	let levels = configured.iter().map(|(level, _)| level.to_string()).collect::<Vec<_>>();
+	assert_eq!(levels, ["/", "/a", "/a/b"]);
+	assert!(configured.iter().all(|(_, outcomes)| outcomes[0].1 == SetOutcome::Changed));
+	assert!(!root.path().join("a").exists());
+	assert_eq!(fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(), "");
+}
+
+#[test]
+#[should_panic(expected = "Controller \"bogus\" is not available in this kernel")]
+fn test_enable_controller_missing_from_kernel() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", ""),
+	]);
+	CGroup::with_root(root.path(), "/a").enable_controller("bogus");
+}
+
+#[test]
+fn test_descendants() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.procs", ""),
+		("a/b/cgroup.procs", ""),
+		("a/b/c/cgroup.procs", ""),
+		("a/b/d/cgroup.procs", ""),
+		("a/e/cgroup.procs", ""),
+		("a/e/f/g/cgroup.procs", ""),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	let names = |cgroups: Vec<CGroup>| cgroups.iter().map(ToString::to_string).collect::<Vec<_>>();
+	assert_eq!(names(cgroup.children()), ["/a/b", "/a/e"]);
+	let expected = ["/a/b", "/a/b/c", "/a/b/d", "/a/e", "/a/e/f", "/a/e/f/g"];
+	assert_eq!(names(cgroup.descendants(1)), expected);
+	assert_eq!(names(cgroup.descendants(4)), expected);
+	assert_eq!(names(cgroup.descendants(1000)), expected);
+	assert!(CGroup::with_root(root.path(), "/a/b/c").descendants(2).is_empty());
+}
+
+/// Times [`CGroup::descendants`] with one and with several jobs over a generated tree of 10,100 groups. Run it with
+/// `cargo test --release -- --ignored --nocapture descendants_timing`, ideally on a slow file system by setting
+/// TMPDIR, since the speedup comes from overlapping directory reads.
+#[test]
+#[ignore]
+fn test_descendants_timing() {
+	let root = tempfile::tempdir().unwrap();
+	for i in 0..100 {
+		for j in 0..100 {
+			fs::create_dir_all(root.path().join(format!("a/g{i}/g{j}"))).unwrap();
+		}
+	}
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	let time = |jobs| {
+		let start = Instant::now();
+		let descendants = cgroup.descendants(jobs);
+		(descendants, start.elapsed())
+	};
+	let (sequential, sequential_time) = time(1);
+	let (parallel, parallel_time) = time(CGroup::MAX_JOBS);
+	assert_eq!(sequential.len(), 10_100);
+	assert_eq!(sequential, parallel);
+	eprintln!(
+		"descendants of {} groups: {sequential_time:?} with 1 job, {parallel_time:?} with {} jobs ({:.1}x)",
+		sequential.len(),
+		CGroup::MAX_JOBS,
+		sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+	);
+}
+
+#[test]
+fn test_create() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", "")]);
+	let b = CGroup::with_root(root.path(), "/a/b/c");
+	assert!(b.create().unwrap());
+	assert!(b.exists().unwrap());
+	assert!(!b.create().unwrap());
+	assert!(!CGroup::with_root(root.path(), "/a").create().unwrap());
+	let long = CGroup::with_root(root.path(), format!("/a/{}", "x".repeat(256)));
+	assert_eq!(long.create().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_dry_run() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", ""), ("a/memory.max", "max\n")]);
+	let a = CGroup::with_root(root.path(), "/a").with_dry_run(true);
+	a.set_restriction("memory.max", "1G");
+	assert_eq!(fs::read_to_string(root.path().join("a/memory.max")).unwrap(), "max\n");
+	let mut b = a.clone();
+	b.append("b");
+	assert!(b.is_dry_run());
+	assert!(b.parent().unwrap().is_dry_run());
+	assert!(b.create().unwrap());
+	assert!(!b.exists().unwrap());
+	assert!(!CGroup::with_root(root.path(), "/a").is_dry_run());
+}
+
+#[test]
+fn test_created_at() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", "")]);
+	let created_at = CGroup::with_root(root.path(), "/a").created_at().unwrap();
+	assert!(created_at.elapsed().unwrap() < Duration::from_secs(60));
+	let e = CGroup::with_root(root.path(), "/b").created_at().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_is_populated() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.events", "populated 1\nfrozen 0\n"),
+		("b/cgroup.events", "populated 0\nfrozen 0\n"),
+	]);
+	assert!(CGroup::with_root(root.path(), "/a").is_populated());
+	assert!(!CGroup::with_root(root.path(), "/b").is_populated());
+	assert!(!CGroup::with_root(root.path(), "/a").is_frozen());
+}
+
+#[test]
+fn test_threaded_processes() {
+	let root = fake_cgroupfs(&[
+		("dom/cgroup.type", "domain\n"),
+		("dom/cgroup.procs", "20\n10\n"),
+		("thr/cgroup.type", "threaded\n"),
+		("thr/cgroup.procs", ""),
+		("thr/cgroup.threads", "31\n"),
+		("empty/cgroup.type", "threaded\n"),
+		("empty/cgroup.procs", ""),
+		("empty/cgroup.threads", ""),
+	]);
+	let dom = CGroup::with_root(root.path(), "/dom");
+	assert_eq!(dom.cgroup_type(), CGroupType::Domain);
+	assert!(dom.has_processes());
+	assert_eq!(dom.procs(), vec![20, 10]);
+	let thr = CGroup::with_root(root.path(), "/thr");
+	assert_eq!(thr.cgroup_type(), CGroupType::Threaded);
+	assert!(thr.has_processes());
+	assert!(!CGroup::with_root(root.path(), "/empty").has_processes());
+	assert_eq!(CGroup::with_root(root.path(), "/").cgroup_type(), CGroupType::Domain);
+	// The main thread of this process maps to itself
+	let pid = std::process::id();
+	fs::write(root.path().join("thr/cgroup.threads"), format!("{pid}\n")).unwrap();
+	assert_eq!(thr.procs(), [pid]);
+	assert_eq!(thr.processes().unwrap(), [pid]);
+	assert_eq!(dom.processes().unwrap(), [20, 10]);
+}
+
+#[test]
+fn test_set_threaded() {
+	let root = fake_cgroupfs(&[("a/cgroup.type", "domain\n")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	a.set_threaded().unwrap();
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/cgroup.type")).unwrap(),
+		"threaded"
+	);
+	assert_eq!(a.cgroup_type(), CGroupType::Threaded);
+	assert_eq!(CGroupType::DomainThreaded.to_string(), "domain threaded");
+	let e = CGroup::with_root(root.path(), "/").set_threaded().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_thread_group_id() {
+	let proc_root = tempfile::tempdir().unwrap();
+	fs::create_dir(proc_root.path().join("31")).unwrap();
+	fs::write(
+		proc_root.path().join("31/status"),
+		"Name:\tworker\nTgid:\t30\nPid:\t31\n",
+	)
+	.unwrap();
+	assert_eq!(thread_group_id(proc_root.path(), 31), Some(30));
+	assert_eq!(thread_group_id(proc_root.path(), 32), None);
+}
+
+#[test]
+fn test_cpuset_partition() {
+	let root = fake_cgroupfs(&[
+		("a/cpuset.cpus", "2-3\n"),
+		("a/cpuset.cpus.partition", "member\n"),
+		("b/cpuset.cpus", "\n"),
+		(
+			"b/cpuset.cpus.partition",
+			"root invalid (Cpu list in cpuset.cpus not exclusive)\n",
+		),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.cpuset_partition(), CpusetPartition::Member);
+	a.set_cpuset_partition(&CpusetPartition::Isolated);
+	assert_eq!(a.cpuset_partition(), CpusetPartition::Isolated);
+	let b = CGroup::with_root(root.path(), "/b");
+	assert_eq!(
+		b.cpuset_partition(),
+		CpusetPartition::RootInvalid("Cpu list in cpuset.cpus not exclusive".to_string())
+	);
+}
+
+#[test]
+#[should_panic(expected = "because cpuset.cpus is empty")]
+fn test_cpuset_partition_without_cpus() {
+	let root = fake_cgroupfs(&[("a/cpuset.cpus", "\n"), ("a/cpuset.cpus.partition", "member\n")]);
+	CGroup::with_root(root.path(), "/a").set_cpuset_partition(&CpusetPartition::Root);
+}
+
+#[test]
+fn test_systemd_unit_names() {
+	assert_eq!(validate_unit_name("myservice.service"), Ok(()));
+	assert_eq!(validate_unit_name("getty@tty1.service"), Ok(()));
+	assert_eq!(validate_unit_name("user-1000.slice"), Ok(()));
+	assert!(validate_unit_name("myservice").is_err());
+	assert!(validate_unit_name(".service").is_err());
+	assert!(validate_unit_name("my service.service").is_err());
+	assert!(validate_unit_name("../etc.service").is_err());
+	assert_eq!(
+		conventional_unit_path("myservice.service"),
+		"/system.slice/myservice.service"
+	);
+	assert_eq!(conventional_unit_path("system.slice"), "/system.slice");
+	assert_eq!(conventional_unit_path("user-1000.slice"), "/user.slice/user-1000.slice");
+	assert_eq!(conventional_unit_path("a-b-c.slice"), "/a.slice/a-b.slice/a-b-c.slice");
+	assert_eq!(conventional_unit_path("-.slice"), "/");
+}
+
+#[test]
+fn test_enable_controller_for_restriction() {
+	assert_eq!(controller_for_key("cpu.max"), Some("cpu"));
+	assert_eq!(controller_for_key("cpuset.cpus.partition"), Some("cpuset"));
+	assert_eq!(controller_for_key("cgroup.max.depth"), None);
+	assert_eq!(controller_for_key("bogus"), None);
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", ""),
+		("cgroup.procs", ""),
+		("grp/cgroup.controllers", ""),
+	]);
+	let grp = CGroup::with_root(root.path(), "/grp");
+	grp.enable_controller_for_restriction("cgroup.max.depth");
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		""
+	);
+	grp.enable_controller_for_restriction("cpu.max");
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"+cpu"
+	);
+}
+
+#[test]
+fn test_wait_until_empty() {
+	let root = fake_cgroupfs(&[("a/cgroup.events", "populated 1\nfrozen 0\n")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	let err = a.wait_until_empty(Duration::from_millis(50)).unwrap_err();
+	assert_eq!(err.timeout, Duration::from_millis(50));
+	let events = root.path().join("a/cgroup.events");
+	let writer = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(150));
+		fs::write(events, "populated 0\nfrozen 0\n").unwrap();
+	});
+	let start = Instant::now();
+	assert_eq!(a.wait_until_empty(Duration::from_secs(10)), Ok(()));
+	assert!(start.elapsed() < Duration::from_secs(5));
+	writer.join().unwrap();
+}
+
+#[test]
+fn test_set_frozen() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.freeze", "0\n"),
+		("a/cgroup.events", "populated 1\nfrozen 0\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	a.set_frozen(true).unwrap();
+	assert_eq!(fs::read_to_string(root.path().join("a/cgroup.freeze")).unwrap(), "1");
+	assert!(a.wait_until_frozen(false, Duration::from_secs(10)));
+	assert!(!a.wait_until_frozen(true, Duration::from_millis(50)));
+	let events = root.path().join("a/cgroup.events");
+	let writer = thread::spawn(move || {
+		thread::sleep(Duration::from_millis(150));
+		fs::write(events, "populated 1\nfrozen 1\n").unwrap();
+	});
+	assert!(a.wait_until_frozen(true, Duration::from_secs(10)));
+	writer.join().unwrap();
+	a.set_frozen(false).unwrap();
+	assert_eq!(fs::read_to_string(root.path().join("a/cgroup.freeze")).unwrap(), "0");
+	let e = CGroup::with_root(root.path(), "/b").set_frozen(true).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_set() {
+	let root = fake_cgroupfs(&[("a/memory.max", "max\n"), ("a/cpu.max", "max 100000\n")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	a.set("memory.max", 1048576);
+	assert_eq!(a.get_restriction("memory.max").unwrap(), "1048576");
+	a.set("memory.max", Limit::Max);
+	assert_eq!(a.get_restriction("memory.max").unwrap(), "max");
+	a.set("cpu.max", Duration::from_millis(50));
+	assert_eq!(a.get_restriction("cpu.max").unwrap(), "50000");
+}
+
+#[test]
+fn test_enable_controller_outcome() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", "cpu\n"),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", "cpu\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.enable_controller("cpu"), SetOutcome::Unchanged);
+	assert_eq!(a.enable_controller("memory"), SetOutcome::Changed);
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"cpu\n+memory"
+	);
+	assert_eq!(a.parent().unwrap().enable_subtree_control("cpu"), SetOutcome::Unchanged);
+}
+
+#[test]
+fn test_disable_subtree_control() {
+	let root = fake_cgroupfs(&[("a/cgroup.subtree_control", "cpu memory\n")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(
+		a.disable_subtree_control(&["memory", "io"]),
+		[
+			("memory".to_string(), SetOutcome::Changed),
+			("io".to_string(), SetOutcome::Unchanged)
+		]
+	);
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/cgroup.subtree_control")).unwrap(),
+		"cpu memory\n-memory"
+	);
+}
+
+#[test]
+fn test_read_events() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.events", "populated 0\nfrozen 1\n"),
+		("a/pids.events", "max 4\n"),
+		("a/memory.events", "low 0\nhigh 9\nmax 0\noom 1\noom_kill 1\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.read_events("pids.events").get("max"), Some(&4));
+	assert!(a.is_frozen());
+	assert!(!a.is_populated());
+	let memory = a.memory_events();
+	assert_eq!((memory.high, memory.oom_kill, memory.oom_group_kill), (9, 1, 0));
+}
+
+#[test]
+fn test_stat() {
+	let root = fake_cgroupfs(&[
+		(
+			"a/cgroup.stat",
+			"nr_descendants 12\nnr_dying_descendants 3\nnr_subsys_cpu 4\n",
+		),
+		("b/cgroup.stat", "nr_descendants 1\n"),
+	]);
+	let stat = CGroup::with_root(root.path(), "/a").stat().unwrap();
+	assert_eq!((stat.nr_descendants, stat.nr_dying_descendants), (12, 3));
+	let e = CGroup::with_root(root.path(), "/b").stat().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+	let e = CGroup::with_root(root.path(), "/c").stat().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_nr_descendants() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.stat", "nr_descendants 12\nnr_dying_descendants 3\n"),
+		("b/cgroup.stat", "nr_dying_descendants 0\n"),
+	]);
+	assert_eq!(CGroup::with_root(root.path(), "/a").nr_descendants(), 12);
+	let result = std::panic::catch_unwind(|| CGroup::with_root(root.path(), "/b").nr_descendants());
+	assert!(result.is_err());
+}
+
+#[test]
+fn test_copy_procs_to() {
+	let root = fake_cgroupfs(&[("src/cgroup.procs", "10\n20\n"), ("dest/cgroup.procs", "")]);
+	let src = CGroup::with_root(root.path(), "/src");
+	let dest = CGroup::with_root(root.path(), "/dest");
+	assert_eq!(src.copy_procs_to(&dest), vec![10, 20]);
+	// Each PID is written to a freshly opened file, so a regular file only keeps the last one
+	assert_eq!(fs::read_to_string(root.path().join("dest/cgroup.procs")).unwrap(), "20");
+}
+
+#[test]
+fn test_file_error() {
+	let root = fake_cgroupfs(&[
+		("cgroup.subtree_control", "cpu\n"),
+		("a/cgroup.subtree_control", ""),
+		("a/b/cgroup.procs", ""),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	let not_found = || io::Error::from(io::ErrorKind::NotFound);
+	// Enabled in the parent, so the kernel just doesn't provide the file
+	assert!(matches!(
+		a.file_error("cpu.foo", not_found()),
+		CGroupError::Unavailable { .. }
+	));
+	assert_eq!(
+		a.file_error("memory.max", not_found()),
+		CGroupError::ControllerNotEnabled {
+			cgroup: "/a".to_string(),
+			file: "memory.max".to_string(),
+			controller: "memory".to_string(),
+			parent: "/".to_string(),
+		}
+	);
+	let b = CGroup::with_root(root.path(), "/a/b");
+	assert!(matches!(
+		b.file_error("cpu.max", not_found()),
+		CGroupError::ControllerNotEnabled { .. }
+	));
+	assert!(matches!(
+		b.file_error("cgroup.foo", not_found()),
+		CGroupError::Unavailable { .. }
+	));
+}
+
+#[test]
+fn test_reset_restrictions() {
+	let root = fake_cgroupfs(&[
+		("a/memory.max", "1048576\n"),
+		("a/cpu.weight", "50\n"),
+		("a/io.bfq.weight", "default 10\n"),
+		("a/cgroup.procs", "123\n"),
+		("a/cpu.pressure", "some total=0\n"),
+		("a/memory.current", "4096\n"),
+	]);
+	let current = root.path().join("a/memory.current");
+	fs::set_permissions(current, fs::Permissions::from_mode(0o444)).unwrap();
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.reset_restrictions(), vec!["io.bfq.weight".to_string()]);
+	assert_eq!(a.get_restriction("memory.max").unwrap(), "max");
+	assert_eq!(a.get_restriction("cpu.weight").unwrap(), "100");
+	assert_eq!(a.get_restriction("cgroup.procs").unwrap(), "123");
+	assert!(!a.reset_restriction("io.bfq.weight"));
+}
+
+#[test]
+fn test_memory_reclaim() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.controllers", "memory\n"),
+		("a/memory.current", "4096\n"),
+		("a/memory.reclaim", ""),
+		("b/cgroup.controllers", "memory\n"),
+		("b/memory.current", "4096\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	// A regular file does not reclaim anything
+	assert_eq!(a.memory_reclaim(1024), 0);
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/memory.reclaim")).unwrap(),
+		"1024"
+	);
+	let b = CGroup::with_root(root.path(), "/b");
+	let result = std::panic::catch_unwind(|| b.memory_reclaim(1024));
+	assert!(result.is_err());
+}
+
+#[test]
+fn test_set_restriction_readback() {
+	let root = fake_cgroupfs(&[("a/cpu.max", "max 100000\n"), ("a/memory.high", "max\n")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	// A regular file does not canonicalize, but the value is read back without surrounding whitespace
+	assert_eq!(a.set_restriction_readback("cpu.max", "50000 100000\n"), "50000 100000");
+	assert_eq!(a.set_restriction_readback("memory.high", " 1048576"), "1048576");
+}
+
+#[test]
+fn test_validate() {
+	let long = "x".repeat(256);
+	assert!(CGroup::from_cgroup_path(format!("/a/{}", &long[1..]))
+		.validate()
+		.is_ok());
+	assert!(CGroup::from_cgroup_path("/a/../b/./c").validate().is_ok());
+	let e = CGroup::from_cgroup_path(format!("/a/{long}/b")).validate().unwrap_err();
+	assert!(
+		e.contains("is 256 bytes long, but the kernel allows at most 255"),
+		"{e}"
+	);
+	let deep = format!("/{}", vec!["y".repeat(200); 25].join("/"));
+	let e = CGroup::from_cgroup_path(deep).validate().unwrap_err();
+	assert!(e.contains("but the kernel allows at most 4095"), "{e}");
+}
+
+#[test]
+#[should_panic(expected = "but the kernel allows at most 255")]
+fn test_append_long_component() {
+	CGroup::from_cgroup_path("/a").append("z".repeat(300));
+}
+
+#[test]
+fn test_missing_controllers_for_restrictions() {
+	let root = fake_cgroupfs(&[("grp/cgroup.controllers", "cpu memory\n")]);
+	let cgroup = CGroup::with_root(root.path(), "/grp");
+	assert_eq!(
+		cgroup.missing_controllers_for_restrictions(&[
+			"bogus.setting",
+			"cpu.max",
+			"io.weight",
+			"bogus.other",
+			"cgroup.max.depth",
+		]),
+		["bogus", "io"]
+	);
+	assert!(cgroup
+		.missing_controllers_for_restrictions(&["memory.max", "cgroup.freeze"])
+		.is_empty());
+}
+
+#[test]
+fn test_enable_controllers_for_restrictions() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu io memory\n"),
+		("cgroup.subtree_control", ""),
+		("cgroup.procs", ""),
+		("grp/cgroup.controllers", "io\n"),
+	]);
+	let outcomes = CGroup::with_root(root.path(), "/grp").enable_controllers_for_restrictions(&[
+		"cpu.max",
+		"memory.max",
+		"io.weight",
+		"memory.high",
+		"cgroup.max.depth",
+	]);
+	assert_eq!(
+		outcomes,
+		[
+			("cpu".to_string(), SetOutcome::Changed),
+			("memory".to_string(), SetOutcome::Changed),
+			("io".to_string(), SetOutcome::Unchanged),
+		]
+	);
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"+cpu+memory"
+	);
+}
+
+#[test]
+fn test_effective_limit() {
+	let root = fake_cgroupfs(&[
+		("cgroup.procs", ""),
+		("a/memory.max", "1073741824\n"),
+		("a/pids.max", "max\n"),
+		("a/b/memory.max", "max\n"),
+		("a/b/pids.max", "max\n"),
+		("a/b/c/memory.max", "2147483648\n"),
+		("a/b/c/pids.max", "max\n"),
+		("a/d/memory.max", "1073741824\n"),
+	]);
+	let c = CGroup::with_root(root.path(), "/a/b/c");
+	let names = |cgroups: Vec<CGroup>| cgroups.iter().map(ToString::to_string).collect::<Vec<_>>();
+	assert_eq!(names(c.ancestors()), ["/a/b", "/a", "/"]);
+	assert_eq!(
+		names(CGroup::with_root(root.path(), "/a/b/../d").ancestors()),
+		["/a", "/"]
+	);
+	let (cgroup, limit) = c.effective_limit("memory.max");
+	assert_eq!(
+		(cgroup.to_string(), limit),
+		("/a".to_string(), Limit::Value(1073741824))
+	);
+	// Nothing is limited, so the group itself is reported
+	let (cgroup, limit) = c.effective_limit("pids.max");
+	assert_eq!((cgroup.to_string(), limit), ("/a/b/c".to_string(), Limit::Max));
+	// On a tie, the closest group wins
+	let (cgroup, _) = CGroup::with_root(root.path(), "/a/d").effective_limit("memory.max");
+	assert_eq!(cgroup.to_string(), "/a/d");
+}
+
+#[test]
+fn test_io_max() {
+	let root = fake_cgroupfs(&[("a/io.max", "")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert!(a.io_max().is_empty());
+	let limits = IoLimits {
+		wbps: Limit::Value(1048576),
+		..Default::default()
+	};
+	a.set_io_max("8:0", &limits);
+	assert_eq!(a.io_max(), HashMap::from([("8:0".to_string(), limits)]));
+	a.set_io_limit(DeviceNumber { major: 8, minor: 16 }, IoLimits::default());
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/io.max")).unwrap(),
+		"8:16 rbps=max wbps=max riops=max wiops=max"
+	);
+}
+
+#[test]
+fn test_cpu_uclamp() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.controllers", "cpu\n"),
+		("a/cpu.uclamp.min", "0.00\n"),
+		("a/cpu.uclamp.max", "max\n"),
+		("b/cgroup.controllers", "cpu\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.cpu_uclamp(), (Percentage::new(0.0).unwrap(), Percentage::MAX));
+	a.set_cpu_uclamp(Percentage::new(12.5).unwrap(), Percentage::new(80.0).unwrap());
+	assert_eq!(
+		fs::read_to_string(root.path().join("a/cpu.uclamp.min")).unwrap(),
+		"12.50"
+	);
+	assert_eq!(
+		a.cpu_uclamp(),
+		(Percentage::new(12.5).unwrap(), Percentage::new(80.0).unwrap())
+	);
+	let result = std::panic::catch_unwind(|| CGroup::with_root(root.path(), "/b").cpu_uclamp());
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert!(
+		message.contains("requires a kernel built with CONFIG_UCLAMP_TASK_GROUP"),
+		"{message}"
+	);
+}
+
+#[test]
+fn test_move_and_verify() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", ""), ("b/cgroup.procs", "")]);
+	CGroup::with_root(root.path(), "/a").move_and_verify(1234).unwrap();
+	// Writes to the null device succeed but are never listed, like a move that the kernel declined
+	fs::remove_file(root.path().join("b/cgroup.procs")).unwrap();
+	std::os::unix::fs::symlink("/dev/null", root.path().join("b/cgroup.procs")).unwrap();
+	let e = CGroup::with_root(root.path(), "/b").move_and_verify(1234).unwrap_err();
+	assert!(e.contains("Process 1234 is not in control group /b"), "{e}");
+}
+
+#[test]
+fn test_list_controllers_with_availability() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpuset cpu io memory pids dmem\n"),
+		("a/cgroup.controllers", "cpu memory pids\n"),
+		("a/cgroup.subtree_control", "memory\n"),
+	]);
+	let status = |name: &str, available_at_root, available, in_subtree| ControllerStatus {
+		name: name.to_string(),
+		available_at_root,
+		available,
+		in_subtree,
+	};
+	assert_eq!(
+		CGroup::with_root(root.path(), "/a").list_controllers_with_availability(),
+		[
+			status("cpuset", true, false, false),
+			status("cpu", true, true, false),
+			status("io", true, false, false),
+			status("memory", true, true, true),
+			status("hugetlb", false, false, false),
+			status("pids", true, true, false),
+			status("rdma", false, false, false),
+			status("misc", false, false, false),
+			status("dmem", true, false, false),
+		]
+	);
+}
+
+#[test]
+fn test_set_restriction_rejected() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", "")]);
+	// Writes to /dev/full fail with ENOSPC
+	std::os::unix::fs::symlink("/dev/full", root.path().join("a/cpu.max")).unwrap();
+	let result = std::panic::catch_unwind(|| CGroup::with_root(root.path(), "/a").set_restriction("cpu.max", "50000"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert!(
+		message.starts_with("Error: The kernel rejected cpu.max=\"50000\" for control group /a: "),
+		"{message}"
+	);
+	assert!(message.contains("cpuset.cpus or cpuset.mems"), "{message}");
+}
+
+#[test]
+fn test_set_restriction_auto() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", "cpu\n"),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", "cpu\n"),
+		("a/cgroup.procs", ""),
+		("a/cpu.weight", "100\n"),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	// Nothing to enable when the file exists
+	cgroup.set_restriction_auto("cpu.weight", "50");
+	assert_eq!(cgroup.get_restriction("cpu.weight").unwrap(), "50");
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"cpu\n"
+	);
+	// The controller is enabled in the parent once; a fake cgroupfs never creates the file, so the retry fails
+	let result = std::panic::catch_unwind(|| cgroup.set_restriction_auto("memory.max", "1G"));
+	let message = *result.unwrap_err().downcast::<String>().unwrap();
+	assert!(
+		message.starts_with("Error: memory.max is unavailable for control group /a even after enabling"),
+		"{message}"
+	);
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"cpu\n+memory"
+	);
+}
+
+#[test]
+fn test_provision() {
+	let root = fake_cgroupfs(&[
+		("cgroup.controllers", "cpu memory\n"),
+		("cgroup.subtree_control", "cpu\n"),
+		("cgroup.procs", ""),
+		("a/cgroup.controllers", "cpu\n"),
+		("a/cgroup.procs", ""),
+		("a/cgroup.threads", ""),
+		("a/cgroup.subtree_control", ""),
+		("a/cpu.weight", "100\n"),
+	]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	let report = cgroup.provision(Some("0:0"), &["cpu"], &[("cpu.weight".to_string(), "20".to_string())]);
+	assert_eq!(
+		report,
+		ProvisionReport {
+			created: false,
+			owner: Some((0, Some(0))),
+			controllers: vec![("cpu".to_string(), SetOutcome::Unchanged)],
+			restrictions: vec![("cpu.weight".to_string(), "20".to_string())],
+		}
+	);
+	assert_eq!(cgroup.get_restriction("cpu.weight").unwrap(), "20");
+	// Controllers needed by restrictions are enabled in the parent; a fake cgroupfs never creates the file, so the
+	// write fails afterwards
+	let result =
+		std::panic::catch_unwind(|| cgroup.provision(None, &[], &[("memory.max".to_string(), "1G".to_string())]));
+	assert!(result.is_err());
+	assert_eq!(
+		fs::read_to_string(root.path().join("cgroup.subtree_control")).unwrap(),
+		"cpu\n+memory"
+	);
+}
+
+#[test]
+fn test_memory_max() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", ""), ("a/memory.max", "max\n")]);
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	assert_eq!(cgroup.memory_max(), Limit::Max);
+	cgroup.set_memory_max(Limit::Value(1 << 30));
+	assert_eq!(cgroup.memory_max(), Limit::Value(1 << 30));
+	// Allowed, with a warning
+	cgroup.set_memory_max(Limit::Value(0));
+	assert_eq!(cgroup.memory_max(), Limit::Value(0));
+	cgroup.set_memory_max(Limit::Max);
+	assert_eq!(cgroup.memory_max(), Limit::Max);
+}
+
+#[test]
+fn test_apply_with_rollback() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.procs", ""),
+		("a/memory.max", "max\n"),
+		("a/memory.high", "max\n"),
+	]);
+	// Files in /proc without a write handler can be read but fail to be written, even by root
+	std::os::unix::fs::symlink("/proc/version", root.path().join("a/pids.max")).unwrap();
+	let cgroup = CGroup::with_root(root.path(), "/a");
+	let changes = |pairs: &[(&str, &str)]| {
+		pairs
+			.iter()
+			.map(|(key, value)| (key.to_string(), value.to_string()))
+			.collect::<Vec<_>>()
+	};
+	let e = cgroup
+		.apply_with_rollback(&changes(&[
+			("memory.max", "1G"),
+			("pids.max", "10"),
+			("memory.high", "512M"),
+		]))
+		.unwrap_err();
+	assert!(
+		matches!(e, CGroupError::Io { ref file, .. } if file == "pids.max"),
+		"{e:?}"
+	);
+	assert_eq!(cgroup.get_restriction("memory.max").unwrap(), "max");
+	assert_eq!(cgroup.get_restriction("memory.high").unwrap(), "max");
+	cgroup
+		.apply_with_rollback(&changes(&[("memory.max", "1G"), ("memory.high", "512M")]))
+		.unwrap();
+	assert_eq!(cgroup.get_restriction("memory.max").unwrap(), "1G");
+	assert_eq!(cgroup.get_restriction("memory.high").unwrap(), "512M");
+}
+
+#[test]
+fn test_weight_tree() {
+	let root = fake_cgroupfs(&[
+		("a/b/cpu.weight", "100\n"),
+		("a/c/cpu.weight", "300\n"),
+		("a/d/cgroup.procs", ""),
+	]);
+	let shares = CGroup::with_root(root.path(), "/a")
+		.weight_tree()
+		.into_iter()
+		.map(|(child, share)| (child.to_string(), share))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		shares,
+		[
+			("/a/b".to_string(), Some((100, 25.0))),
+			("/a/c".to_string(), Some((300, 75.0))),
+			("/a/d".to_string(), None)
+		]
+	);
+}
+
+#[test]
+fn test_delegation_chain() {
+	let root = fake_cgroupfs(&[
+		("cgroup.subtree_control", "cpu memory\n"),
+		("a/cgroup.subtree_control", "cpu\n"),
+		("a/b/cgroup.subtree_control", "memory\n"),
+		("a/b/c/cgroup.subtree_control", ""),
+	]);
+	let chain = CGroup::with_root(root.path(), "/a/b/c")
+		.delegation_chain("cpu")
+		.into_iter()
+		.map(|(level, enabled)| (level.to_string(), enabled))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		chain,
+		[
+			("/".to_string(), true),
+			("/a".to_string(), true),
+			("/a/b".to_string(), false),
+			("/a/b/c".to_string(), false)
+		]
+	);
+}
+
+#[test]
+fn test_describe() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.controllers", "cpu memory\n"),
+		("a/cgroup.procs", "12\n34\n56\n"),
+		("a/memory.max", "2147483648\n"),
+		("a/memory.current", "419430400\n"),
+		("a/cpu.max", "max 100000\n"),
+		("b/cgroup.controllers", "cpu\n"),
+		("b/cgroup.procs", "12\n"),
+		("b/cpu.max", "150000 100000\n"),
+		("c/cgroup.controllers", ""),
+		("c/cgroup.procs", ""),
+	]);
+	assert_eq!(
+		CGroup::with_root(root.path(), "/a").describe(),
+		"Control group /a can use the cpu and memory controllers, has a memory limit of 2.0GiB (currently using 400.0MiB), has no CPU limit, and holds 3 processes."
+	);
+	assert_eq!(
+		CGroup::with_root(root.path(), "/b").describe(),
+		"Control group /b can use the cpu controller, has a CPU limit of 1.5 CPUs, and holds 1 process."
+	);
+	assert_eq!(
+		CGroup::with_root(root.path(), "/c").describe(),
+		"Control group /c can use no controllers and holds no processes."
+	);
+}
+
+#[test]
+fn test_snapshot() {
+	use std::collections::BTreeMap;
+	let root = fake_cgroupfs(&[
+		("a/cgroup.controllers", "cpu memory\n"),
+		("a/cgroup.subtree_control", "memory\n"),
+		("a/cgroup.procs", "123\n"),
+		("a/cgroup.kill", ""),
+		("a/cpu.weight", "100\n"),
+		("a/cpu.stat", "usage_usec 10\nuser_usec 6\nsystem_usec 4\n"),
+		("a/memory.max", "max\n"),
+		("a/memory.current", "4096\n"),
+		("a/b/cgroup.procs", ""),
+	]);
+	for file in ["cpu.stat", "memory.current"] {
+		fs::set_permissions(root.path().join("a").join(file), fs::Permissions::from_mode(0o444)).unwrap();
+	}
+	let snapshot = CGroup::with_root(root.path(), "/a").snapshot();
+	let map = |entries: &[(&str, &str)]| {
+		entries
+			.iter()
+			.map(|(key, value)| (key.to_string(), value.to_string()))
+			.collect::<BTreeMap<_, _>>()
+	};
+	assert_eq!(
+		snapshot,
+		CGroupSnapshot {
+			cgroup: "/a".to_string(),
+			controllers: vec!["cpu".to_string(), "memory".to_string()],
+			subtree_control: vec!["memory".to_string()],
+			stats: map(&[
+				("cpu.stat", "usage_usec 10\nuser_usec 6\nsystem_usec 4"),
+				("memory.current", "4096")
+			]),
+			settings: map(&[("cpu.weight", "100"), ("memory.max", "max")]),
+		}
+	);
+	assert!(serde_json::to_string(&snapshot)
+		.unwrap()
+		.contains(r#""settings":{"cpu.weight":"100","memory.max":"max"}"#));
+}
+
+#[test]
+fn test_processes() {
+	let root = fake_cgroupfs(&[
+		("a/cgroup.procs", "30\n10\n"),
+		("a/b/cgroup.procs", "20\n"),
+		("a/b/c/cgroup.procs", ""),
+		("a/cpu.max", "max 100000\n"),
+		("d/cgroup.procs", "1\nx\n"),
+	]);
+	let a = CGroup::with_root(root.path(), "/a");
+	assert_eq!(a.processes().unwrap(), [30, 10]);
+	assert_eq!(a.processes_recursive().unwrap(), [10, 20, 30]);
+	assert!(CGroup::with_root(root.path(), "/a/b/c")
+		.processes_recursive()
+		.unwrap()
+		.is_empty());
+	let e = CGroup::with_root(root.path(), "/d").processes().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+	let e = CGroup::with_root(root.path(), "/e").processes_recursive().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_kill_all() {
+	use std::os::unix::process::ExitStatusExt;
+	let root = fake_cgroupfs(&[("a/cgroup.kill", ""), ("b/cgroup.procs", ""), ("b/c/cgroup.procs", "")]);
+	CGroup::with_root(root.path(), "/a").kill_all().unwrap();
+	assert_eq!(fs::read_to_string(root.path().join("a/cgroup.kill")).unwrap(), "1");
+
+	// Without cgroup.kill, the processes of the group and its descendants are killed one by one
+	let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+	fs::write(root.path().join("b/c/cgroup.procs"), format!("{}\n", child.id())).unwrap();
+	CGroup::with_root(root.path(), "/b").kill_all().unwrap();
+	assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGKILL));
+
+	let e = CGroup::with_root(root.path(), "/d").kill_all().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_classify_with_descendants() {
+	let root = fake_cgroupfs(&[("a/cgroup.procs", "")]);
+	let a = CGroup::with_root(root.path(), "/a");
+	let mut child = process::Command::new("sleep").arg("10").spawn().unwrap();
+	let moved = a.classify_with_descendants(process::id());
+	let _ = child.kill();
+	let _ = child.wait();
+	// Other tests may also have children running, so only check for the one started here
+	assert!(moved.contains(&child.id()), "{moved:?}");
+	assert!(!moved.contains(&process::id()));
 }
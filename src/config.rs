@@ -0,0 +1,109 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Default flags from configuration files
+
+use crate::internal;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Defaults for command-line flags and the cgroup2 mount point, read from `config.toml` in the directories of
+/// [`internal::config_dirs`].
+///
+/// Flags passed on the command line take precedence over the environment (such as `NO_COLOR` or `CG2_ROOT`), which
+/// takes precedence over these defaults, which take precedence over the built-in defaults. The user's file takes
+/// precedence over the system's, one key at a time.
+///
+/// ```toml
+/// auto = true
+/// color = "never"
+/// error_format = "json"
+/// root = "/cgroup2"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Default for `--auto`. Since `--auto` is a switch, the command line cannot turn it off again.
+	pub auto: Option<bool>,
+	/// Default for `--color`: "auto", "always", or "never".
+	pub color: Option<String>,
+	/// Default for `--error-format`: "text" or "json".
+	pub error_format: Option<String>,
+	/// Mount point of the cgroup2 file system, used by both tools if `CG2_ROOT` is not set.
+	pub root: Option<PathBuf>,
+}
+
+impl Config {
+	/// Parses the contents of a configuration file.
+	pub fn parse(contents: &str) -> Result<Self, String> {
+		toml::from_str(contents).map_err(|e| e.message().to_string())
+	}
+
+	/// Loads and merges the configuration files that exist. Missing files are skipped.
+	pub fn load() -> Result<Self, String> {
+		let mut config = Self::default();
+		for dir in internal::config_dirs() {
+			if let Some(file) = Self::load_file(&dir.join("config.toml"))? {
+				config = config.or(file);
+			}
+		}
+		Ok(config)
+	}
+
+	fn load_file(path: &Path) -> Result<Option<Self>, String> {
+		match fs::read_to_string(path) {
+			Ok(contents) => Self::parse(&contents)
+				.map(Some)
+				.map_err(|e| format!("{}: {e}", path.display())),
+			Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+			Err(e) => Err(format!("{}: {e}", path.display())),
+		}
+	}
+
+	/// Fills the keys that are unset in this configuration from a configuration with lower precedence.
+	fn or(self, lower: Self) -> Self {
+		Self {
+			auto: self.auto.or(lower.auto),
+			color: self.color.or(lower.color),
+			error_format: self.error_format.or(lower.error_format),
+			root: self.root.or(lower.root),
+		}
+	}
+}
+
+#[test]
+fn test_parse_config() {
+	assert_eq!(Config::parse(""), Ok(Config::default()));
+	let config = Config::parse("auto = true\ncolor = \"never\"\n").unwrap();
+	assert_eq!(config.auto, Some(true));
+	assert_eq!(config.color.as_deref(), Some("never"));
+	assert_eq!(config.error_format, None);
+	assert_eq!(Config::parse("root = \"/cgroup2\"\n").unwrap().root, Some(PathBuf::from("/cgroup2")));
+	assert!(Config::parse("auto = \"yes\"\n").is_err());
+	assert!(Config::parse("quiet = true\n").is_err());
+	assert!(Config::parse("auto = \n").is_err());
+}
+
+#[test]
+fn test_merge_config() {
+	let user = Config::parse("color = \"always\"\n").unwrap();
+	let system = Config::parse("auto = true\ncolor = \"never\"\nroot = \"/cgroup2\"\n").unwrap();
+	let merged = user.or(system);
+	assert_eq!(merged.auto, Some(true));
+	assert_eq!(merged.color.as_deref(), Some("always"));
+	assert_eq!(merged.root, Some(PathBuf::from("/cgroup2")));
+}
@@ -0,0 +1,182 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conformance tests of the parsers against interface files captured from real kernels
+//!
+//! The fixtures are in `tests/fixtures`. A fixture with a version suffix, such as `cpu.stat-5.4`, was captured on
+//! that older kernel, which reports fewer fields than current kernels.
+
+use crate::events;
+use crate::pressure;
+use crate::restriction;
+use crate::usage;
+use crate::CGroupStat;
+use crate::IoLimits;
+use crate::Limit;
+use crate::MemoryEvents;
+use crate::MemoryNumaStat;
+use crate::MemoryStat;
+
+macro_rules! fixture {
+	($name:literal) => {
+		include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/", $name))
+	};
+}
+
+#[test]
+fn test_memory_stat() {
+	let stat = MemoryStat::from_stat(events::parse(fixture!("memory.stat")));
+	assert_eq!(stat.anon, 58720256);
+	assert_eq!(stat.file, 209276928);
+	assert_eq!(stat.kernel_stack, 425984);
+	assert_eq!(stat.slab, 4875376);
+	assert_eq!(stat.sock, 12288);
+	assert_eq!(stat.shmem, 1617920);
+	assert_eq!(stat.pgfault, 1487325);
+	assert_eq!(stat.pgmajfault, 1372);
+	assert_eq!(stat.other.len(), 45);
+	assert_eq!(stat.other["file_mapped"], 77619200);
+	assert_eq!(stat.other["sec_pagetables"], 0);
+
+	// 4.19 has neither "kernel" nor the split workingset counters
+	let stat = MemoryStat::from_stat(events::parse(fixture!("memory.stat-4.19")));
+	assert_eq!(stat.anon, 2715648);
+	assert_eq!(stat.pgmajfault, 99);
+	assert!(!stat.other.contains_key("kernel"));
+	assert_eq!(stat.other["workingset_refault"], 0);
+}
+
+#[test]
+fn test_memory_events() {
+	let memory_events = MemoryEvents::from_events(&events::parse(fixture!("memory.events")));
+	assert_eq!(
+		memory_events,
+		MemoryEvents {
+			low: 0,
+			high: 118,
+			max: 7,
+			oom: 1,
+			oom_kill: 1,
+			oom_group_kill: 0,
+		}
+	);
+
+	// oom_group_kill was added in 5.17
+	let memory_events = MemoryEvents::from_events(&events::parse(fixture!("memory.events-5.4")));
+	assert_eq!(memory_events, MemoryEvents::default());
+}
+
+#[test]
+fn test_memory_numa_stat() {
+	let stat = MemoryNumaStat::parse(fixture!("memory.numa_stat")).unwrap();
+	assert_eq!(stat.nodes.len(), 2);
+	assert_eq!(stat.nodes[&0].len(), 27);
+	assert_eq!(stat.nodes[&0]["anon"], 41943040);
+	assert_eq!(stat.nodes[&1]["file"], 58281984);
+	assert_eq!(stat.nodes[&1]["workingset_nodereclaim"], 0);
+}
+
+#[test]
+fn test_cgroup_stat() {
+	let stat = events::parse(fixture!("cgroup.stat"));
+	assert_eq!(stat.len(), 20);
+	assert_eq!(stat["nr_descendants"], 4);
+	assert_eq!(stat["nr_dying_descendants"], 1);
+	assert_eq!(stat["nr_subsys_cpu"], 5);
+	assert_eq!(stat["nr_dying_subsys_memory"], 1);
+	assert_eq!(
+		CGroupStat::from_stat(&stat),
+		Ok(CGroupStat {
+			nr_descendants: 4,
+			nr_dying_descendants: 1,
+		})
+	);
+
+	// The per-controller counts were added in 6.11
+	let stat = events::parse(fixture!("cgroup.stat-5.4"));
+	assert_eq!(stat.len(), 2);
+	assert_eq!(stat["nr_descendants"], 2);
+	assert_eq!(
+		CGroupStat::from_stat(&stat).map(|stat| stat.nr_dying_descendants),
+		Ok(0)
+	);
+}
+
+#[test]
+fn test_cpu_stat() {
+	let stat = events::parse(fixture!("cpu.stat"));
+	assert_eq!(stat.len(), 9);
+	assert_eq!(stat["usage_usec"], 48913577);
+	assert_eq!(stat["nr_throttled"], 412);
+	assert_eq!(stat["throttled_usec"], 20644198);
+	assert_eq!(stat["core_sched.force_idle_usec"], 0);
+
+	// Without the cpu controller enabled, only the usage is reported
+	let stat = events::parse(fixture!("cpu.stat-5.4"));
+	assert_eq!(stat.len(), 3);
+	assert_eq!(stat["system_usec"], 294652);
+	assert!(!stat.contains_key("nr_throttled"));
+}
+
+#[test]
+fn test_pressure() {
+	assert_eq!(
+		pressure::parse_totals(fixture!("cpu.pressure")),
+		Ok((86402301, 12906714))
+	);
+	assert_eq!(pressure::parse_totals(fixture!("cpu.pressure-5.10")), Ok((3121885, 0)));
+	assert_eq!(pressure::parse_totals(fixture!("memory.pressure")), Ok((0, 0)));
+}
+
+#[test]
+fn test_cpu_max() {
+	let contents = fixture!("cpu.max");
+	assert_eq!(restriction::check_value("cpu.max", contents.trim()), Ok(()));
+	let (quota, period) = contents.trim().split_once(' ').unwrap();
+	assert_eq!(quota.parse(), Ok(Limit::Value(50000)));
+	assert_eq!(period.parse::<u64>(), Ok(100000));
+}
+
+#[test]
+fn test_io_max() {
+	let devices = IoLimits::parse(fixture!("io.max")).unwrap();
+	assert_eq!(devices.len(), 2);
+	assert_eq!(
+		devices["8:16"],
+		IoLimits {
+			rbps: Limit::Value(2097152),
+			wiops: Limit::Value(120),
+			..Default::default()
+		}
+	);
+	assert_eq!(
+		devices["259:0"],
+		IoLimits {
+			wbps: Limit::Value(1048576),
+			riops: Limit::Value(1000),
+			..Default::default()
+		}
+	);
+}
+
+#[test]
+fn test_proc_cgroups() {
+	let controllers = usage::parse(fixture!("cgroups")).unwrap();
+	assert_eq!(controllers.len(), 14);
+	assert_eq!(controllers[0].name, "cpuset");
+	assert_eq!(controllers[13].name, "misc");
+	assert!(controllers
+		.iter()
+		.all(|c| c.hierarchy == 0 && c.num_cgroups == 128 && c.enabled));
+}
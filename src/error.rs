@@ -0,0 +1,217 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Errors returned or raised by control group operations
+
+use crate::internal;
+use crate::CGroup;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::panic;
+use std::time::Duration;
+
+/// A failed operation on a control group.
+///
+/// Methods of [`CGroup`] panic with the message of this error. When a binary opts into structured errors, the panic
+/// payload is the error itself, so that it can be reported in a machine-readable form. Serializing gives an object
+/// such as `{"error":"permission_denied","cgroup":"/a/b","file":"cgroup.procs"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum CGroupError {
+	/// The control group does not exist.
+	NotFound { cgroup: String },
+	/// An interface file of the control group could not be opened, read, or written due to its permissions.
+	PermissionDenied { cgroup: String, file: String },
+	/// The kernel does not provide an interface file, usually because its controller is not enabled.
+	Unavailable { cgroup: String, file: String },
+	/// An interface file is missing because its controller is not enabled in `cgroup.subtree_control` of the parent.
+	ControllerNotEnabled {
+		cgroup: String,
+		file: String,
+		controller: String,
+		parent: String,
+	},
+	/// The kernel rejected a value written to an interface file, with a common cause such as an out of range value.
+	Rejected {
+		cgroup: String,
+		file: String,
+		value: String,
+		/// Name of the error number, such as "ENOSPC".
+		errno: String,
+		message: String,
+	},
+	/// Any other failure while accessing an interface file.
+	Io {
+		cgroup: String,
+		file: String,
+		message: String,
+	},
+}
+
+impl CGroupError {
+	pub(crate) fn not_found(cgroup: &CGroup) -> Self {
+		Self::NotFound {
+			cgroup: cgroup.to_string(),
+		}
+	}
+
+	/// Classifies an I/O error that occurred while accessing the given interface file.
+	pub(crate) fn from_io(cgroup: &CGroup, file: &str, e: io::Error) -> Self {
+		let cgroup = cgroup.to_string();
+		let file = file.to_string();
+		match e.kind() {
+			io::ErrorKind::PermissionDenied => Self::PermissionDenied { cgroup, file },
+			io::ErrorKind::NotFound => Self::Unavailable { cgroup, file },
+			_ => Self::Io {
+				cgroup,
+				file,
+				message: e.to_string(),
+			},
+		}
+	}
+
+	/// Explains a kernel rejection of a value written to an interface file, or returns `None` if the error is not a
+	/// known rejection.
+	pub(crate) fn rejected(cgroup: &CGroup, file: &str, value: &str, e: &io::Error) -> Option<Self> {
+		let errno = REJECTIONS
+			.iter()
+			.find(|(errno, _, _)| e.raw_os_error() == Some(*errno))?
+			.1;
+		Some(Self::Rejected {
+			cgroup: cgroup.to_string(),
+			file: file.to_string(),
+			value: value.to_string(),
+			errno: errno.to_string(),
+			message: e.to_string(),
+		})
+	}
+
+	/// Panics with this error, either as a prose message or, if enabled, as a structured payload.
+	pub(crate) fn raise(self) -> ! {
+		if internal::structured_errors() {
+			panic::panic_any(self)
+		}
+		panic!("Error: {self}")
+	}
+}
+
+impl fmt::Display for CGroupError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::NotFound { cgroup } => write!(f, "Control group {cgroup} does not exist"),
+			Self::PermissionDenied { cgroup, file } if file == "cgroup.procs" => write!(f, "Permission denied: cannot write to cgroup.procs of control group {cgroup}. Moving a process also requires write access to cgroup.procs of the common ancestor of its current and new control groups."),
+			Self::PermissionDenied { cgroup, file } => {
+				write!(f, "Permission denied: cannot access {file} of control group {cgroup}")
+			}
+			Self::Unavailable { cgroup, file } if cgroup == "/" && !file.starts_with("cgroup.") => write!(f, "{file} is unavailable for the root control group, which has no resource limits. Set it in a child control group instead."),
+			Self::Unavailable { cgroup, file } => write!(f, "{file} is unavailable for control group {cgroup}"),
+			Self::ControllerNotEnabled {
+				cgroup,
+				file,
+				controller,
+				parent,
+			} => write!(f, "{file} is unavailable for control group {cgroup} because the \"{controller}\" controller is not enabled for children of {parent}. Enable it there with `cg2util control {parent} +{controller}`, or pass --auto."),
+			Self::Rejected {
+				cgroup,
+				file,
+				value,
+				errno,
+				message,
+			} => {
+				let hint = REJECTIONS.iter().find(|(_, name, _)| name == errno).map_or("", |(_, _, hint)| hint);
+				write!(f, "The kernel rejected {file}=\"{value}\" for control group {cgroup}: {message}. {hint}")
+			}
+			Self::Io { cgroup, file, message } => {
+				write!(f, "While accessing {file} of control group {cgroup}: {message}")
+			}
+		}
+	}
+}
+
+impl std::error::Error for CGroupError {}
+
+/// Errors that the kernel returns for values written to interface files, with their names and likely causes.
+const REJECTIONS: &[(i32, &str, &str)] = &[
+	(libc::EINVAL, "EINVAL", "The value is not in the format that the file expects, or is out of range. See <https://docs.kernel.org/admin-guide/cgroup-v2.html> for the format."),
+	(libc::ERANGE, "ERANGE", "The value is out of the range that the file accepts."),
+	(libc::ENOSPC, "ENOSPC", "A limit leaves no room for the change: for example, cpuset.cpus or cpuset.mems of the control group is empty, or cpu.max asks for more bandwidth than the real-time scheduler leaves."),
+	(libc::EBUSY, "EBUSY", "The control group is in use in a way that prevents the change, for example because it or its children have processes or depend on the previous value."),
+	(libc::EOPNOTSUPP, "EOPNOTSUPP", "The control group does not support this change, for example because it is threaded and the controller only works in domain control groups, or because the file cannot be changed at this level of the hierarchy."),
+	(libc::EDEADLK, "EDEADLK", "The kernel detected a conflicting operation in progress. Try again."),
+];
+
+/// A control group was still populated when [`CGroup::wait_until_empty`] gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutError {
+	/// The control group that was waited on.
+	pub cgroup: String,
+	/// How long was waited.
+	pub timeout: Duration,
+}
+
+impl fmt::Display for TimeoutError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"Control group {} still has processes after {:?}",
+			self.cgroup, self.timeout
+		)
+	}
+}
+
+impl std::error::Error for TimeoutError {}
+
+#[test]
+fn test_from_io() {
+	let cgroup = CGroup::from_cgroup_path("/a/b");
+	assert_eq!(
+		CGroupError::from_io(&cgroup, "cgroup.procs", io::ErrorKind::PermissionDenied.into()),
+		CGroupError::PermissionDenied {
+			cgroup: "/a/b".to_string(),
+			file: "cgroup.procs".to_string()
+		}
+	);
+	assert_eq!(
+		CGroupError::from_io(&cgroup, "cpu.max", io::ErrorKind::NotFound.into()),
+		CGroupError::Unavailable {
+			cgroup: "/a/b".to_string(),
+			file: "cpu.max".to_string()
+		}
+	);
+	assert!(matches!(
+		CGroupError::from_io(&cgroup, "cpu.max", io::Error::from_raw_os_error(libc::EINVAL)),
+		CGroupError::Io { .. }
+	));
+}
+
+#[test]
+fn test_rejected() {
+	let cgroup = CGroup::from_cgroup_path("/a/b");
+	let e = CGroupError::rejected(&cgroup, "cpu.max", "50000", &io::Error::from_raw_os_error(libc::ENOSPC)).unwrap();
+	assert!(matches!(&e, CGroupError::Rejected { errno, .. } if errno == "ENOSPC"));
+	let message = e.to_string();
+	assert!(
+		message.starts_with("The kernel rejected cpu.max=\"50000\" for control group /a/b: "),
+		"{message}"
+	);
+	assert!(
+		message.contains("cpuset.cpus or cpuset.mems of the control group is empty"),
+		"{message}"
+	);
+	assert_eq!(
+		CGroupError::rejected(&cgroup, "cpu.max", "50000", &io::ErrorKind::PermissionDenied.into()),
+		None
+	);
+}
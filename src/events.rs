@@ -0,0 +1,46 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A parser for flat keyed files, such as `cgroup.events` and `memory.events`
+
+use std::collections::HashMap;
+
+/// Parses the "key value" lines of an events file into a map.
+///
+/// Lines that don't have a key and a numeric value are skipped, so a new kind of line in a future kernel does not
+/// prevent reading the others.
+pub(crate) fn parse(contents: &str) -> HashMap<String, u64> {
+	contents
+		.lines()
+		.filter_map(|line| {
+			let (key, value) = line.split_once(' ')?;
+			Some((key.to_string(), value.trim().parse().ok()?))
+		})
+		.collect()
+}
+
+#[test]
+fn test_parse() {
+	let cgroup_events = parse("populated 1\nfrozen 0\n");
+	assert_eq!(cgroup_events.get("populated"), Some(&1));
+	assert_eq!(cgroup_events.get("frozen"), Some(&0));
+	let memory_events = parse("low 0\nhigh 25\nmax 3\noom 0\noom_kill 2\noom_group_kill 0\n");
+	assert_eq!(memory_events.len(), 6);
+	assert_eq!(memory_events.get("high"), Some(&25));
+	let pids_events = parse("max 17\n");
+	assert_eq!(pids_events.get("max"), Some(&17));
+	let malformed = parse("max\nhigh many\nlow 4\n\n");
+	assert_eq!(malformed.len(), 1);
+	assert_eq!(malformed.get("low"), Some(&4));
+}
@@ -14,7 +14,39 @@
 
 //! Internal shared functions
 
+use crate::CGroup;
+use crate::Limit;
 use core::fmt;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CString;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Read;
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 pub fn os_check(args: impl fmt::Debug) {
 	if cfg!(not(target_os = "linux")) {
@@ -23,3 +55,995 @@ pub fn os_check(args: impl fmt::Debug) {
 		std::process::exit(1);
 	}
 }
+
+static STRUCTURED_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Makes failed control group operations panic with a [`crate::CGroupError`] payload instead of a prose message.
+pub fn enable_structured_errors() {
+	STRUCTURED_ERRORS.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn structured_errors() -> bool {
+	STRUCTURED_ERRORS.load(Ordering::Relaxed)
+}
+
+static IO_TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Makes reads and writes of interface files fail with an [`io::ErrorKind::TimedOut`] error if they take longer than
+/// `timeout`, instead of blocking indefinitely on a hung file system.
+pub fn set_io_timeout(timeout: Duration) {
+	let _ = IO_TIMEOUT.set(timeout);
+}
+
+/// Runs a file system operation, giving up after the timeout set with [`set_io_timeout`], if any.
+pub(crate) fn with_io_timeout<T: Send + 'static>(op: impl FnOnce() -> io::Result<T> + Send + 'static) -> io::Result<T> {
+	with_timeout(IO_TIMEOUT.get().copied(), op)
+}
+
+/// A blocking read or write cannot be interrupted, so with a timeout the operation runs on its own thread, which is
+/// left behind if it times out.
+fn with_timeout<T: Send + 'static>(
+	timeout: Option<Duration>,
+	op: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+	let Some(timeout) = timeout else {
+		return op();
+	};
+	let (sender, receiver) = mpsc::channel();
+	thread::spawn(move || {
+		let _ = sender.send(op());
+	});
+	match receiver.recv_timeout(timeout) {
+		Ok(result) => result,
+		Err(mpsc::RecvTimeoutError::Timeout) => Err(io::Error::new(
+			io::ErrorKind::TimedOut,
+			format!("no response from the file system after {timeout:?}"),
+		)),
+		Err(mpsc::RecvTimeoutError::Disconnected) => Err(io::Error::other("the file system operation panicked")),
+	}
+}
+
+/// Appends a JSON record to the file at `path` for every change that this process makes to a control group. The
+/// records identify the invocation by its command line.
+pub fn enable_audit_log(path: PathBuf) {
+	let command = env::args_os()
+		.map(|arg| arg.to_string_lossy().into_owned())
+		.collect::<Vec<_>>()
+		.join(" ");
+	crate::audit::enable(path, command);
+}
+
+/// Prints an error message and exits with status 1. With structured errors enabled, it panics with the message
+/// instead, so that the panic hook reports it in the requested format.
+pub fn exit_with_error(message: &str) -> ! {
+	if structured_errors() {
+		panic!("{message}");
+	}
+	println!("{message}");
+	std::process::exit(1);
+}
+
+/// Exits with an explanation if the current process is in `cgroup` or one of its descendants, unless `force` is set.
+///
+/// Operations such as deleting, freezing, or killing a control group would otherwise remove, suspend, or kill the
+/// tool itself, which fails in confusing ways (for example, a frozen tool never exits).
+pub fn refuse_if_contains_current(cgroup: &CGroup, action: &str, force: bool) {
+	if force || !cgroup.contains_current() {
+		return;
+	}
+	exit_with_error(&format!("Error: Refusing to {action} control group {cgroup} because this process is running inside it, so the operation would also affect this process. Pass --force to {action} it anyway."));
+}
+
+/// Parses a "key=value" pair. Only the first "=" separates the key; the value is kept verbatim, including commas,
+/// spaces, and further "=" signs, because files such as `io.max` and `cpuset.cpus` use them.
+pub fn parse_key_value(input: &str) -> Result<(String, String), &'static str> {
+	let (key, value) = input.split_once('=').ok_or("expected key=value")?;
+	if !key.chars().all(|c| matches!(c, '_' | '.' | 'a'..='z')) {
+		return Err("key contains invalid characters");
+	}
+	if !key.contains('.') {
+		return Err("key must be of the form CONTROLLER.RESTRICTION");
+	}
+	Ok((key.to_string(), value.to_string()))
+}
+
+pub use crate::config::Config;
+
+/// Returns the directories that hold cg2tools configuration, in order of precedence: the user's
+/// (`$XDG_CONFIG_HOME/cg2tools` or `~/.config/cg2tools`), then the system's (`/etc/cg2tools`).
+pub fn config_dirs() -> Vec<PathBuf> {
+	let mut dirs = Vec::new();
+	if let Some(config_dir) = env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+	{
+		dirs.push(config_dir.join("cg2tools"));
+	}
+	dirs.push(PathBuf::from("/etc/cg2tools"));
+	dirs
+}
+
+static CONFIGURED_CGROUPFS_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+static DETECTED_CGROUPFS_ROOT: OnceLock<PathBuf> = OnceLock::new();
+
+/// Sets the mount point of the cgroup2 file system from the `root` key of the configuration, for [`cgroupfs_root`].
+pub fn set_configured_cgroupfs_root(root: PathBuf) {
+	let _ = CONFIGURED_CGROUPFS_ROOT.set(root);
+}
+
+/// Returns the mount point of the cgroup2 file system: the value of the `CG2_ROOT` environment variable if set, or
+/// else the one set with [`set_configured_cgroupfs_root`], or else the mount point found by
+/// [`crate::CGroup::detect_mount_point`], which is only looked up once.
+pub fn cgroupfs_root() -> PathBuf {
+	if let Some(root) = env::var_os("CG2_ROOT") {
+		return PathBuf::from(root);
+	}
+	if let Some(root) = CONFIGURED_CGROUPFS_ROOT.get() {
+		return root.clone();
+	}
+	DETECTED_CGROUPFS_ROOT
+		.get_or_init(|| crate::CGroup::detect_mount_point().unwrap_or_else(|_| PathBuf::from("/sys/fs/cgroup")))
+		.clone()
+}
+
+/// Returns the mount point of procfs: the value of the `CG2_PROC_ROOT` environment variable if set, or "/proc".
+pub fn proc_root() -> PathBuf {
+	env::var_os("CG2_PROC_ROOT")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| PathBuf::from("/proc"))
+}
+
+/// Resolves an owner in the form "USER" or "USER:GROUP" to a user ID and a group ID, like chown(1). Users and groups
+/// may be names or numeric IDs. Without a group, a user name resolves to its primary group, and a numeric user ID
+/// leaves the group unchanged (`None`).
+pub(crate) fn resolve_owner(owner: &str) -> Result<(u32, Option<u32>), String> {
+	let (user, group) = match owner.split_once(':') {
+		Some((user, group)) => (user, Some(group)),
+		None => (owner, None),
+	};
+	let (uid, primary_gid) = match user.parse::<u32>() {
+		Ok(uid) => (uid, None),
+		Err(_) => {
+			let name = CString::new(user).map_err(|_| format!("invalid user name \"{user}\""))?;
+			let mut buf = vec![0 as libc::c_char; 16384];
+			// SAFETY: passwd is plain data, for which all zeroes is a valid value
+			let mut pwd: libc::passwd = unsafe { mem::zeroed() };
+			let mut result = std::ptr::null_mut();
+			// SAFETY: pwd and result are valid for writes, and only numeric fields are read after buf is dropped
+			unsafe { libc::getpwnam_r(name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+			if result.is_null() {
+				return Err(format!("no such user \"{user}\""));
+			}
+			(pwd.pw_uid, Some(pwd.pw_gid))
+		}
+	};
+	let gid = match group {
+		None => primary_gid,
+		Some(group) => Some(match group.parse::<u32>() {
+			Ok(gid) => gid,
+			Err(_) => {
+				let name = CString::new(group).map_err(|_| format!("invalid group name \"{group}\""))?;
+				let mut buf = vec![0 as libc::c_char; 16384];
+				// SAFETY: group is plain data, for which all zeroes is a valid value
+				let mut grp: libc::group = unsafe { mem::zeroed() };
+				let mut result = std::ptr::null_mut();
+				// SAFETY: grp and result are valid for writes, and only numeric fields are read after buf is dropped
+				unsafe { libc::getgrnam_r(name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+				if result.is_null() {
+					return Err(format!("no such group \"{group}\""));
+				}
+				grp.gr_gid
+			}
+		}),
+	};
+	Ok((uid, gid))
+}
+
+/// Returns the IDs of the processes whose name (from `/proc/<pid>/comm`) contains `pattern`, in ascending order.
+///
+/// The kernel truncates process names to 15 bytes. The current process is never included.
+pub fn find_pids_by_name(pattern: &str) -> io::Result<Vec<u32>> {
+	let mut pids = find_pids_by_name_in(&proc_root(), pattern)?;
+	pids.retain(|pid| *pid != std::process::id());
+	Ok(pids)
+}
+
+fn find_pids_by_name_in(proc_root: &Path, pattern: &str) -> io::Result<Vec<u32>> {
+	let mut pids = Vec::new();
+	for entry in fs::read_dir(proc_root)? {
+		let entry = entry?;
+		// Skip entries like "self" and "sys" that are not processes
+		let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+			continue;
+		};
+		let comm = match fs::read_to_string(entry.path().join("comm")) {
+			Ok(comm) => comm,
+			// The process exited during the scan
+			Err(e) if e.kind() == io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESRCH) => continue,
+			Err(e) => return Err(e),
+		};
+		if comm.trim_end_matches('\n').contains(pattern) {
+			pids.push(pid);
+		}
+	}
+	pids.sort_unstable();
+	Ok(pids)
+}
+
+/// Returns the process ID referred to by a pidfd, from the "Pid" field of `/proc/self/fdinfo/<fd>`.
+///
+/// The field requires Linux 5.5 or newer; on older kernels this returns an [`io::ErrorKind::Unsupported`] error.
+pub fn pidfd_pid(fd: RawFd) -> io::Result<u32> {
+	let path = proc_root().join("self").join("fdinfo").join(fd.to_string());
+	parse_fdinfo_pid(&fs::read_to_string(path)?)
+}
+
+fn parse_fdinfo_pid(contents: &str) -> io::Result<u32> {
+	let Some(pid) = contents.lines().find_map(|line| line.strip_prefix("Pid:")) else {
+		return Err(io::Error::new(
+			io::ErrorKind::Unsupported,
+			"not a pidfd, or the kernel is older than Linux 5.5 and does not report the process ID of pidfds",
+		));
+	};
+	match pid.trim().parse::<i64>() {
+		// Since Linux 5.15, -1 means that the process has exited
+		Ok(-1) => Err(io::Error::from_raw_os_error(libc::ESRCH)),
+		Ok(0) => Err(io::Error::other(
+			"the process is not in the PID namespace of this process",
+		)),
+		Ok(pid) => u32::try_from(pid).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+		Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+	}
+}
+
+/// Returns an error if the process referred to by a pidfd has exited, by sending it the null signal.
+///
+/// Unlike `kill(pid, 0)`, this cannot be confused by the process ID being reused. Requires Linux 5.1 or newer.
+pub fn pidfd_check_alive(fd: RawFd) -> io::Result<()> {
+	// SAFETY: pidfd_send_signal does not retain any of its arguments, and the siginfo pointer may be null
+	let result = unsafe {
+		libc::syscall(
+			libc::SYS_pidfd_send_signal,
+			fd,
+			0,
+			std::ptr::null::<libc::siginfo_t>(),
+			0,
+		)
+	};
+	if result == 0 {
+		Ok(())
+	} else {
+		Err(io::Error::last_os_error())
+	}
+}
+
+/// Kernel ABI of `struct clone_args` for clone3(2), up to the `cgroup` field that was added in Linux 5.7.
+#[repr(C)]
+#[derive(Default)]
+struct CloneArgs {
+	flags: u64,
+	pidfd: u64,
+	child_tid: u64,
+	parent_tid: u64,
+	exit_signal: u64,
+	stack: u64,
+	stack_size: u64,
+	tls: u64,
+	set_tid: u64,
+	set_tid_size: u64,
+	cgroup: u64,
+}
+
+/// Flag for clone3(2) to start the child in the control group given by [`CloneArgs::cgroup`].
+const CLONE_INTO_CGROUP: u64 = 0x200000000;
+
+/// Starts a command as a child process directly in the control group whose directory is open as `cgroup_dir`, using
+/// clone3(2) with `CLONE_INTO_CGROUP`, and returns its process ID.
+///
+/// The child inherits stdio and the environment, and must be reaped with [`wait_pid`]. It starts with no blocked
+/// signals and with `SIGPIPE` restored to its default action, which the Rust runtime ignores in this process, and a
+/// program name without "/" is looked up in `PATH`. Kernels older than Linux 5.7 do not support this, in which case an
+/// [`io::ErrorKind::Unsupported`] error is returned.
+pub fn spawn_into_cgroup(cgroup_dir: &File, command: &[OsString]) -> io::Result<u32> {
+	// Only async-signal-safe functions may be called between clone3 and exec, so prepare everything beforehand,
+	// including the lookup in PATH and the environment that execvp(3) would otherwise do in the child
+	let Some(program) = command.first() else {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty command"));
+	};
+	let program = CString::new(find_program(program)?.into_os_string().into_vec())
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+	let args = command
+		.iter()
+		.map(|arg| CString::new(arg.as_bytes()))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+	let mut argv = args.iter().map(|arg| arg.as_ptr()).collect::<Vec<_>>();
+	argv.push(std::ptr::null());
+	let vars = env::vars_os()
+		.map(|(key, value)| {
+			let mut var = key.into_vec();
+			var.push(b'=');
+			var.extend_from_slice(value.as_bytes());
+			CString::new(var)
+		})
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+	let mut envp = vars.iter().map(|var| var.as_ptr()).collect::<Vec<_>>();
+	envp.push(std::ptr::null());
+	// SAFETY: sigemptyset initializes the set
+	let no_signals = unsafe {
+		let mut set = mem::zeroed();
+		libc::sigemptyset(&mut set);
+		set
+	};
+	// SAFETY: A zeroed sigaction is valid, and sigemptyset initializes its mask
+	let default_action = unsafe {
+		let mut action: libc::sigaction = mem::zeroed();
+		action.sa_sigaction = libc::SIG_DFL;
+		libc::sigemptyset(&mut action.sa_mask);
+		action
+	};
+	// The child reports a failed exec through this pipe; a successful exec closes it
+	let mut fds = [0; 2];
+	// SAFETY: fds has room for the two file descriptors
+	if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	// SAFETY: pipe2 succeeded, so both file descriptors are open and owned by nobody else
+	let (mut reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+	let mut clone_args = CloneArgs {
+		flags: CLONE_INTO_CGROUP,
+		exit_signal: libc::SIGCHLD as u64,
+		cgroup: cgroup_dir.as_raw_fd() as u64,
+		..Default::default()
+	};
+	// SAFETY: clone_args is a valid clone_args struct of the given size. Without CLONE_VM, the child gets a copy of
+	// the address space like with fork(2), with only the calling thread. Locks held by other threads of this process,
+	// such as the allocator's, stay locked in the child, which is why it only calls async-signal-safe functions.
+	let pid = unsafe {
+		libc::syscall(
+			libc::SYS_clone3,
+			&mut clone_args as *mut CloneArgs,
+			mem::size_of::<CloneArgs>(),
+		)
+	};
+	if pid == 0 {
+		// SAFETY: In the child, only async-signal-safe functions are called before exec or _exit, on data that was
+		// prepared before clone3
+		unsafe {
+			libc::sigprocmask(libc::SIG_SETMASK, &no_signals, std::ptr::null_mut());
+			// Ignored signals stay ignored across exec, so undo the runtime's SIG_IGN for SIGPIPE like
+			// std::process::Command does
+			libc::sigaction(libc::SIGPIPE, &default_action, std::ptr::null_mut());
+			libc::execve(program.as_ptr(), argv.as_ptr(), envp.as_ptr());
+			let errno = io::Error::last_os_error().raw_os_error().unwrap_or(0).to_ne_bytes();
+			libc::write(writer.as_raw_fd(), errno.as_ptr().cast(), errno.len());
+			libc::_exit(127);
+		}
+	}
+	if pid < 0 {
+		let e = io::Error::last_os_error();
+		return match e.raw_os_error() {
+			// Linux 5.2 and older have no clone3; Linux 5.6 and older reject the cgroup field
+			Some(libc::ENOSYS) | Some(libc::E2BIG) => Err(io::Error::new(io::ErrorKind::Unsupported, e)),
+			_ => Err(e),
+		};
+	}
+	drop(writer);
+	let pid = pid as u32;
+	let mut errno = [0; 4];
+	match reader.read_exact(&mut errno) {
+		Ok(()) => {
+			let _ = wait_pid(pid);
+			Err(io::Error::from_raw_os_error(i32::from_ne_bytes(errno)))
+		}
+		Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(pid),
+		Err(e) => Err(e),
+	}
+}
+
+/// Returns the file that exec should run for `program`, like execvp(3): a name containing "/" is used as is, and any
+/// other name is looked up in the directories of `PATH`.
+fn find_program(program: &OsStr) -> io::Result<PathBuf> {
+	if program.as_bytes().contains(&b'/') {
+		return Ok(PathBuf::from(program));
+	}
+	// The same default as execvp(3) when PATH is unset
+	let path = env::var_os("PATH").unwrap_or_else(|| OsString::from("/bin:/usr/bin"));
+	for dir in env::split_paths(&path) {
+		// An empty entry stands for the current directory
+		let candidate = dir.join(program);
+		if fs::metadata(&candidate).is_ok_and(|metadata| metadata.is_file() && metadata.mode() & 0o111 != 0) {
+			return Ok(candidate);
+		}
+	}
+	Err(io::Error::from_raw_os_error(libc::ENOENT))
+}
+
+/// Waits for a child process to exit and returns its exit status.
+pub fn wait_pid(pid: u32) -> io::Result<ExitStatus> {
+	let mut status = 0;
+	loop {
+		// SAFETY: status is a valid pointer for the exit status
+		if unsafe { libc::waitpid(pid as libc::pid_t, &mut status, 0) } >= 0 {
+			break;
+		}
+		let e = io::Error::last_os_error();
+		if e.kind() != io::ErrorKind::Interrupted {
+			return Err(e);
+		}
+	}
+	Ok(ExitStatus::from_raw(status))
+}
+
+/// Formats a time as an RFC 3339 timestamp in UTC with millisecond precision, such as "2026-10-16T08:30:00.250Z".
+pub fn format_timestamp(time: SystemTime) -> String {
+	let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+	let secs = since_epoch.as_secs();
+	let (days, secs_of_day) = (secs / 86400, secs % 86400);
+	// Civil date from days since 1970-01-01, after Howard Hinnant's days_from_civil inverse
+	let z = days as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + i64::from(month <= 2);
+	format!(
+		"{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+		secs_of_day / 3600,
+		secs_of_day / 60 % 60,
+		secs_of_day % 60,
+		since_epoch.subsec_millis()
+	)
+}
+
+/// The control group that a command operates on, given either as its first positional argument or with --cgroup.
+#[derive(clap::Args, Debug)]
+pub struct CGroupArg {
+	/// Name of the control group. May be relative (appended to the control group of the current process) or absolute (starting with "/").
+	#[arg(id = "cgroup_pos", value_name = "CGROUP", required = true)]
+	positional: Option<String>,
+
+	/// Name of the control group, instead of passing it as the first argument.
+	#[arg(id = "cgroup", long = "cgroup", value_name = "CGROUP", conflicts_with = "cgroup_pos")]
+	flag: Option<String>,
+}
+
+impl CGroupArg {
+	/// Returns the name of the control group, from whichever form was given.
+	pub fn name(&self) -> &str {
+		self.flag
+			.as_deref()
+			.or(self.positional.as_deref())
+			.expect("clap requires the control group in one of the two forms")
+	}
+}
+
+/// The control groups that a command operates on, given either as positional arguments or with a repeated --cgroup
+/// option. Declared with the same IDs as [`CGroupArg`], so [`parse_args`] handles both the same way.
+#[derive(clap::Args, Debug)]
+pub struct CGroupArgs {
+	/// Names of the control groups. Each may be relative (appended to the control group of the current process) or absolute (starting with "/").
+	#[arg(id = "cgroup_pos", value_name = "CGROUP", required = true)]
+	positional: Vec<String>,
+
+	/// Name of a control group, instead of passing the names as positional arguments. May be repeated.
+	#[arg(id = "cgroup", long = "cgroup", value_name = "CGROUP", conflicts_with = "cgroup_pos")]
+	flag: Vec<String>,
+}
+
+impl CGroupArgs {
+	/// Returns the names of the control groups, from whichever form was given.
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		// The two forms conflict, so only one of them holds names
+		self.positional.iter().chain(&self.flag).map(String::as_str)
+	}
+}
+
+/// Parses a command line whose control groups are declared with [`CGroupArg`] or [`CGroupArgs`].
+///
+/// clap assigns positional arguments in order, so with --cgroup it would take the first of the remaining positional
+/// arguments, such as a process ID or the command of cg2exec, as the control group. If --cgroup was given, the
+/// command line is therefore parsed with the positional form turned into an optional hidden option, and that result
+/// or error is returned. Otherwise, the command line is parsed as declared, which also reports the errors, such as
+/// passing both forms.
+pub fn parse_args<T: clap::Parser>(args: impl IntoIterator<Item = OsString>) -> Result<T, clap::Error> {
+	let args = args.into_iter().collect::<Vec<_>>();
+	let command = without_cgroup_positional(T::command());
+	// Look for --cgroup while ignoring errors, such as a missing argument, that the strict parse below reports
+	let has_flag = command
+		.clone()
+		.ignore_errors(true)
+		.try_get_matches_from(&args)
+		.is_ok_and(|matches| has_cgroup_flag(&matches));
+	if has_flag {
+		let mut matches = command.try_get_matches_from(&args)?;
+		return T::from_arg_matches_mut(&mut matches);
+	}
+	T::try_parse_from(args)
+}
+
+/// Turns the "cgroup_pos" argument of `command` and its subcommands into an optional hidden option, so that it no
+/// longer takes up a positional argument.
+fn without_cgroup_positional(mut command: clap::Command) -> clap::Command {
+	if command.get_arguments().any(|arg| arg.get_id() == "cgroup_pos") {
+		command = command.mut_arg("cgroup_pos", |arg| arg.index(None).long("cgroup-positional").hide(true).required(false));
+	}
+	let subcommands = command
+		.get_subcommands()
+		.map(|subcommand| subcommand.get_name().to_string())
+		.collect::<Vec<_>>();
+	for name in subcommands {
+		command = command.mut_subcommand(name, without_cgroup_positional);
+	}
+	command
+}
+
+/// Returns whether --cgroup was given to the command or the subcommand that `matches` belong to.
+fn has_cgroup_flag(matches: &clap::ArgMatches) -> bool {
+	matches.ids().any(|id| id == "cgroup") || matches.subcommand().is_some_and(|(_, matches)| has_cgroup_flag(matches))
+}
+
+/// Returns the set of signals that ask a process to terminate: SIGINT, SIGTERM, and SIGHUP.
+fn termination_signals() -> libc::sigset_t {
+	// SAFETY: sigemptyset initializes the set before sigaddset reads it
+	unsafe {
+		let mut set = mem::zeroed();
+		libc::sigemptyset(&mut set);
+		for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+			libc::sigaddset(&mut set, signal);
+		}
+		set
+	}
+}
+
+/// Blocks SIGINT, SIGTERM, and SIGHUP for the current thread, so that they stay pending until received with
+/// [`wait_for_termination`] instead of ending the process. Call this before making a change that must be undone.
+pub fn block_termination_signals() {
+	let set = termination_signals();
+	// SAFETY: set is initialized, and the previous mask is not requested
+	unsafe {
+		libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+	}
+}
+
+/// Sleeps until the timeout elapses or one of the signals blocked by [`block_termination_signals`] arrives, and
+/// returns the signal if one arrived.
+pub fn wait_for_termination(timeout: Duration) -> Option<i32> {
+	let set = termination_signals();
+	let deadline = Instant::now() + timeout;
+	loop {
+		let remaining = deadline.saturating_duration_since(Instant::now());
+		let timespec = libc::timespec {
+			tv_sec: remaining.as_secs() as libc::time_t,
+			tv_nsec: remaining.subsec_nanos() as libc::c_long,
+		};
+		// SAFETY: set and timespec are initialized, and the siginfo pointer may be null
+		let signal = unsafe { libc::sigtimedwait(&set, std::ptr::null_mut(), &timespec) };
+		if signal > 0 {
+			return Some(signal);
+		}
+		// Interrupted by another signal: keep waiting for the rest of the timeout
+		if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+			return None;
+		}
+	}
+}
+
+/// Forwards the signals blocked by [`block_termination_signals`] to the process `pid` from a new thread, so that a
+/// wrapper can wait for the process to shut down and report its exit status instead of ending first.
+///
+/// The new thread inherits the blocked signals, so call [`block_termination_signals`] first.
+pub fn forward_termination_signals(pid: u32) {
+	thread::spawn(move || loop {
+		if let Some(signal) = wait_for_termination(Duration::from_secs(3600)) {
+			// SAFETY: kill has no memory safety requirements
+			unsafe {
+				libc::kill(pid as libc::pid_t, signal);
+			}
+		}
+	});
+}
+
+/// Returns the IDs of the descendants of a process (its children, their children, and so on) in ascending order, from
+/// the parent process IDs in `/proc/<pid>/stat`.
+pub fn descendant_pids(pid: u32) -> io::Result<Vec<u32>> {
+	descendant_pids_in(&proc_root(), pid)
+}
+
+fn descendant_pids_in(proc_root: &Path, pid: u32) -> io::Result<Vec<u32>> {
+	let mut children = HashMap::<u32, Vec<u32>>::new();
+	for entry in fs::read_dir(proc_root)? {
+		let entry = entry?;
+		let Some(child) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+			continue;
+		};
+		let stat = match fs::read_to_string(entry.path().join("stat")) {
+			Ok(stat) => stat,
+			// The process exited during the scan
+			Err(e) if e.kind() == io::ErrorKind::NotFound || e.raw_os_error() == Some(libc::ESRCH) => continue,
+			Err(e) => return Err(e),
+		};
+		// The name in parentheses may itself contain spaces and parentheses, so parse from the last ")": the state
+		// comes next, then the parent process ID
+		let ppid = stat
+			.rsplit_once(')')
+			.and_then(|(_, rest)| rest.split_whitespace().nth(1))
+			.and_then(|ppid| ppid.parse::<u32>().ok());
+		if let Some(ppid) = ppid {
+			children.entry(ppid).or_default().push(child);
+		}
+	}
+	let mut descendants = Vec::new();
+	let mut queue = vec![pid];
+	while let Some(parent) = queue.pop() {
+		for &child in children.get(&parent).into_iter().flatten() {
+			descendants.push(child);
+			queue.push(child);
+		}
+	}
+	descendants.sort_unstable();
+	Ok(descendants)
+}
+
+/// Parses a duration with a unit suffix: "ms", "s", "m", "h", or "d", such as "500ms" or "2h".
+pub fn parse_duration(input: &str) -> Result<Duration, &'static str> {
+	let split = input
+		.find(|c: char| !c.is_ascii_digit())
+		.ok_or("missing unit (ms, s, m, h, or d)")?;
+	let (number, unit) = input.split_at(split);
+	let number: u64 = number
+		.parse()
+		.map_err(|_| "expected a number followed by a unit, such as 30s")?;
+	let duration = match unit {
+		"ms" => Duration::from_millis(number),
+		"s" => Duration::from_secs(number),
+		"m" => Duration::from_secs(number * 60),
+		"h" => Duration::from_secs(number * 3600),
+		"d" => Duration::from_secs(number * 86400),
+		_ => return Err("unknown unit; expected ms, s, m, h, or d"),
+	};
+	Ok(duration)
+}
+
+/// Parses a number of bytes with an optional binary suffix: "k", "M", "G", or "T" in either case, optionally followed
+/// by "i", such as "512k", "1M", or "1Mi" for 1048576. "max" means no limit.
+pub fn parse_size(input: &str) -> Result<Limit, &'static str> {
+	if input == "max" {
+		return Ok(Limit::Max);
+	}
+	let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+	let (number, unit) = input.split_at(split);
+	let number: u64 = number
+		.parse()
+		.map_err(|_| "expected a number with an optional unit, such as 512k or 1M, or \"max\"")?;
+	let shift = match unit.to_ascii_lowercase().as_str() {
+		"" => 0,
+		"k" | "ki" => 10,
+		"m" | "mi" => 20,
+		"g" | "gi" => 30,
+		"t" | "ti" => 40,
+		_ => return Err("unknown unit; expected k, M, G, or T, optionally followed by i"),
+	};
+	number
+		.checked_mul(1 << shift)
+		.map(Limit::Value)
+		.ok_or("size is too large")
+}
+
+/// Formats a duration compactly with its two most significant units, such as "3d4h", "15m", or "42s".
+pub fn format_duration(duration: Duration) -> String {
+	let secs = duration.as_secs();
+	let (days, hours, minutes, seconds) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+	match (days, hours, minutes) {
+		(0, 0, 0) => format!("{seconds}s"),
+		(0, 0, _) if seconds == 0 => format!("{minutes}m"),
+		(0, 0, _) => format!("{minutes}m{seconds}s"),
+		(0, _, 0) => format!("{hours}h"),
+		(0, _, _) => format!("{hours}h{minutes}m"),
+		(_, 0, _) => format!("{days}d"),
+		(_, _, _) => format!("{days}d{hours}h"),
+	}
+}
+
+/// Colors for highlighting terminal output.
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+	Red,
+	Green,
+	Yellow,
+	Cyan,
+}
+
+/// Returns whether colors should be used by default: stdout is a terminal and `NO_COLOR` is unset or empty.
+pub fn stdout_supports_color() -> bool {
+	let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+	!no_color && io::stdout().is_terminal()
+}
+
+/// Wraps text in ANSI escape codes for the given color, or returns it unchanged if `enabled` is false.
+pub fn paint(text: &str, color: Color, enabled: bool) -> String {
+	if !enabled {
+		return text.to_string();
+	}
+	let code = match color {
+		Color::Red => 31,
+		Color::Green => 32,
+		Color::Yellow => 33,
+		Color::Cyan => 36,
+	};
+	format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+/// Formats a number of bytes with an IEC suffix and one decimal, such as "512B", "1.5KiB", or "1.8GiB".
+/// [`Limit::Max`] is formatted as "max".
+pub fn format_bytes(bytes: Limit) -> String {
+	const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+	let Limit::Value(bytes) = bytes else {
+		return "max".to_string();
+	};
+	if bytes < 1024 {
+		return format!("{bytes}B");
+	}
+	let mut value = bytes as f64 / 1024.0;
+	let mut unit = 0;
+	while value >= 1024.0 && unit < UNITS.len() - 1 {
+		value /= 1024.0;
+		unit += 1;
+	}
+	format!("{value:.1}{}", UNITS[unit])
+}
+
+/// Writes a file such that readers never observe it partially written.
+///
+/// The contents are written to a temporary file in the same directory, which is then renamed over `path`.
+pub fn write_file_atomic(path: &Path, contents: &str) -> io::Result<()> {
+	let Some(file_name) = path.file_name() else {
+		return Err(io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"));
+	};
+	let mut tmp_name = file_name.to_os_string();
+	tmp_name.push(format!(".{}.tmp", std::process::id()));
+	let tmp_path = path.with_file_name(tmp_name);
+	fs::write(&tmp_path, contents)?;
+	fs::rename(&tmp_path, path).map_err(|e| {
+		let _ = fs::remove_file(&tmp_path);
+		e
+	})
+}
+
+#[test]
+fn test_write_file_atomic() {
+	let dir = tempfile::tempdir().unwrap();
+	let path = dir.path().join("cg2exec.pid");
+	write_file_atomic(&path, "123\n").unwrap();
+	assert_eq!(fs::read_to_string(&path).unwrap(), "123\n");
+	write_file_atomic(&path, "45\n").unwrap();
+	assert_eq!(fs::read_to_string(&path).unwrap(), "45\n");
+	assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_find_pids_by_name_in() {
+	let dir = tempfile::tempdir().unwrap();
+	for (pid, comm) in [("1", "systemd"), ("42", "stress"), ("7", "stress-ng"), ("99", "bash")] {
+		fs::create_dir(dir.path().join(pid)).unwrap();
+		fs::write(dir.path().join(pid).join("comm"), format!("{comm}\n")).unwrap();
+	}
+	// Not a process
+	fs::create_dir(dir.path().join("sys")).unwrap();
+	// A process that exited before its comm file was read
+	fs::create_dir(dir.path().join("123")).unwrap();
+	assert_eq!(find_pids_by_name_in(dir.path(), "stress").unwrap(), [7, 42]);
+	assert_eq!(find_pids_by_name_in(dir.path(), "bash").unwrap(), [99]);
+	assert!(find_pids_by_name_in(dir.path(), "nothing").unwrap().is_empty());
+}
+
+#[test]
+fn test_format_duration() {
+	assert_eq!(format_duration(Duration::from_millis(500)), "0s");
+	assert_eq!(format_duration(Duration::from_secs(42)), "42s");
+	assert_eq!(format_duration(Duration::from_secs(15 * 60)), "15m");
+	assert_eq!(format_duration(Duration::from_secs(15 * 60 + 3)), "15m3s");
+	assert_eq!(format_duration(Duration::from_secs(2 * 3600 + 59)), "2h");
+	assert_eq!(format_duration(Duration::from_secs(2 * 3600 + 5 * 60)), "2h5m");
+	assert_eq!(format_duration(Duration::from_secs(3 * 86400 + 59 * 60)), "3d");
+	assert_eq!(format_duration(Duration::from_secs(3 * 86400 + 4 * 3600)), "3d4h");
+}
+
+#[test]
+fn test_parse_duration() {
+	assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+	assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+	assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+	assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+	assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604800)));
+	assert!(parse_duration("30").is_err());
+	assert!(parse_duration("s").is_err());
+	assert!(parse_duration("1.5h").is_err());
+	assert!(parse_duration("3w").is_err());
+}
+
+#[test]
+fn test_parse_size() {
+	assert_eq!(parse_size("1048576"), Ok(Limit::Value(1048576)));
+	assert_eq!(parse_size("512k"), Ok(Limit::Value(524288)));
+	assert_eq!(parse_size("1M"), Ok(Limit::Value(1048576)));
+	assert_eq!(parse_size("2g"), Ok(Limit::Value(2 << 30)));
+	assert_eq!(parse_size("512Mi"), Ok(Limit::Value(512 << 20)));
+	assert_eq!(parse_size("1Ki"), Ok(Limit::Value(1024)));
+	assert_eq!(parse_size("0"), Ok(Limit::Value(0)));
+	assert_eq!(parse_size("max"), Ok(Limit::Max));
+	assert!(parse_size("").is_err());
+	assert!(parse_size("1.5M").is_err());
+	assert!(parse_size("1MB").is_err());
+	assert!(parse_size("20000000T").is_err());
+}
+
+#[test]
+fn test_format_bytes() {
+	assert_eq!(format_bytes(Limit::Value(0)), "0B");
+	assert_eq!(format_bytes(Limit::Value(1023)), "1023B");
+	assert_eq!(format_bytes(Limit::Value(1024)), "1.0KiB");
+	assert_eq!(format_bytes(Limit::Value(1536)), "1.5KiB");
+	assert_eq!(format_bytes(Limit::Value(1932735283)), "1.8GiB");
+	assert_eq!(format_bytes(Limit::Value(5 << 40)), "5.0TiB");
+	assert_eq!(format_bytes(Limit::Value(u64::MAX)), "16.0EiB");
+	assert_eq!(format_bytes(Limit::Max), "max");
+}
+
+#[test]
+fn test_parse_key_value() {
+	let pair = |key: &str, value: &str| Ok((key.to_string(), value.to_string()));
+	assert_eq!(parse_key_value("cpu.weight=150"), pair("cpu.weight", "150"));
+	assert_eq!(parse_key_value("a.b=c,d"), pair("a.b", "c,d"));
+	assert_eq!(parse_key_value("cpuset.cpus=0-3,8"), pair("cpuset.cpus", "0-3,8"));
+	assert_eq!(
+		parse_key_value("io.max=8:16 rbps=2097152 wbps=max"),
+		pair("io.max", "8:16 rbps=2097152 wbps=max")
+	);
+	assert_eq!(parse_key_value("memory.max="), pair("memory.max", ""));
+	assert!(parse_key_value("cpu.weight").is_err());
+	assert!(parse_key_value("cpu=150").is_err());
+	assert!(parse_key_value("CPU.weight=150").is_err());
+}
+
+#[test]
+fn test_paint() {
+	assert_eq!(paint("grp", Color::Green, false), "grp");
+	assert_eq!(paint("grp", Color::Green, true), "\x1b[32mgrp\x1b[0m");
+	assert_eq!(paint("", Color::Cyan, true), "\x1b[36m\x1b[0m");
+}
+
+#[test]
+fn test_parse_fdinfo_pid() {
+	assert_eq!(
+		parse_fdinfo_pid("pos:\t0\nflags:\t02000002\nmnt_id:\t15\nino:\t1057\nPid:\t4242\nNSpid:\t4242\n").unwrap(),
+		4242
+	);
+	assert_eq!(
+		parse_fdinfo_pid("Pid:\t-1\n").unwrap_err().raw_os_error(),
+		Some(libc::ESRCH)
+	);
+	assert!(parse_fdinfo_pid("Pid:\t0\n").is_err());
+	// A regular file, or a pidfd on a kernel older than 5.5
+	assert_eq!(
+		parse_fdinfo_pid("pos:\t0\nflags:\t02100000\nmnt_id:\t26\n")
+			.unwrap_err()
+			.kind(),
+		io::ErrorKind::Unsupported
+	);
+}
+
+#[test]
+fn test_spawn_into_cgroup_exec_failure() {
+	// Whether or not the kernel supports clone3 into this directory, a missing program is never started
+	let dir = File::open(std::env::temp_dir()).unwrap();
+	assert!(spawn_into_cgroup(&dir, &[OsString::from("/nonexistent/cg2tools-test")]).is_err());
+	assert!(spawn_into_cgroup(&dir, &[]).is_err());
+}
+
+#[test]
+fn test_find_program() {
+	let sh = find_program(OsStr::new("sh")).unwrap();
+	assert!(sh.is_absolute() && sh.ends_with("sh"), "{sh:?}");
+	assert_eq!(find_program(OsStr::new("./run")).unwrap(), PathBuf::from("./run"));
+	assert_eq!(
+		find_program(OsStr::new("cg2tools-nonexistent")).unwrap_err().raw_os_error(),
+		Some(libc::ENOENT)
+	);
+}
+
+#[test]
+#[allow(clippy::zombie_processes)] // reaped by wait_pid instead of Child::wait
+fn test_wait_pid_reports_signal() {
+	use std::process::Command;
+	let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+	child.kill().unwrap();
+	let status = wait_pid(child.id()).unwrap();
+	assert_eq!(status.code(), None);
+	assert_eq!(status.signal(), Some(libc::SIGKILL));
+}
+
+#[test]
+fn test_format_timestamp() {
+	assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+	assert_eq!(
+		format_timestamp(UNIX_EPOCH + Duration::from_millis(951_782_400_250)),
+		"2000-02-29T00:00:00.250Z"
+	);
+	assert_eq!(
+		format_timestamp(UNIX_EPOCH + Duration::from_secs(1_792_139_399)),
+		"2026-10-16T08:29:59.000Z"
+	);
+}
+
+#[test]
+fn test_wait_for_termination() {
+	block_termination_signals();
+	assert_eq!(wait_for_termination(Duration::from_millis(10)), None);
+	// SAFETY: SIGHUP is blocked, so it stays pending for this thread
+	unsafe {
+		libc::raise(libc::SIGHUP);
+	}
+	assert_eq!(wait_for_termination(Duration::from_secs(10)), Some(libc::SIGHUP));
+}
+
+#[test]
+fn test_descendant_pids_in() {
+	let dir = tempfile::tempdir().unwrap();
+	for (pid, comm, ppid) in [
+		(1, "systemd", 0),
+		(10, "bash", 1),
+		(11, "make", 10),
+		(12, "cc (1) x", 11),
+		(13, "ld", 11),
+		(20, "sshd", 1),
+		(21, "vim", 20),
+	] {
+		fs::create_dir(dir.path().join(pid.to_string())).unwrap();
+		let stat = format!("{pid} ({comm}) S {ppid} {pid} {pid} 0 -1 4194560\n");
+		fs::write(dir.path().join(pid.to_string()).join("stat"), stat).unwrap();
+	}
+	// A process that exited before its stat file was read
+	fs::create_dir(dir.path().join("99")).unwrap();
+	assert_eq!(descendant_pids_in(dir.path(), 10).unwrap(), [11, 12, 13]);
+	assert_eq!(descendant_pids_in(dir.path(), 20).unwrap(), [21]);
+	assert!(descendant_pids_in(dir.path(), 13).unwrap().is_empty());
+}
+
+#[test]
+fn test_with_timeout() {
+	assert_eq!(with_timeout(None, || Ok(1)).unwrap(), 1);
+	assert_eq!(with_timeout(Some(Duration::from_secs(10)), || Ok(2)).unwrap(), 2);
+	let e = with_timeout(Some(Duration::from_millis(10)), || {
+		thread::sleep(Duration::from_secs(1));
+		Ok(3)
+	})
+	.unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+	let e = with_timeout(Some(Duration::from_secs(10)), || {
+		Err::<(), _>(io::ErrorKind::NotFound.into())
+	})
+	.unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
+
+#[test]
+fn test_resolve_owner() {
+	assert_eq!(resolve_owner("1000"), Ok((1000, None)));
+	assert_eq!(resolve_owner("1000:100"), Ok((1000, Some(100))));
+	assert_eq!(resolve_owner("root"), Ok((0, Some(0))));
+	assert_eq!(resolve_owner("root:root"), Ok((0, Some(0))));
+	assert_eq!(
+		resolve_owner("no-such-user-cg2tools"),
+		Err("no such user \"no-such-user-cg2tools\"".to_string())
+	);
+	assert_eq!(
+		resolve_owner("0:no-such-group-cg2tools"),
+		Err("no such group \"no-such-group-cg2tools\"".to_string())
+	);
+}
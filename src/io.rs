@@ -0,0 +1,219 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for the io controller's interface files
+
+use crate::Limit;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The number of a block device, which identifies it in `io.max` and the other io interface files.
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::DeviceNumber;
+///
+/// let device: DeviceNumber = "8:16".parse().unwrap();
+/// assert_eq!(device, DeviceNumber { major: 8, minor: 16 });
+/// assert_eq!(device.to_string(), "8:16");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeviceNumber {
+	/// The major number, which identifies the driver, such as 8 for SCSI disks.
+	pub major: u32,
+	/// The minor number, which identifies the device or partition within the driver.
+	pub minor: u32,
+}
+
+impl DeviceNumber {
+	/// Returns the number of the block device at `path`, such as "/dev/sda", following symbolic links such as those in
+	/// "/dev/disk/by-id".
+	pub fn from_path(path: impl AsRef<Path>) -> io::Result<Self> {
+		let metadata = fs::metadata(path.as_ref())?;
+		if !metadata.file_type().is_block_device() {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a block device"));
+		}
+		Ok(Self::from_dev(metadata.rdev()))
+	}
+
+	/// Splits a device number as returned by stat(2) in the same way as the major(3) and minor(3) macros.
+	fn from_dev(dev: u64) -> Self {
+		Self {
+			major: (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff)) as u32,
+			minor: (((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff)) as u32,
+		}
+	}
+}
+
+impl FromStr for DeviceNumber {
+	type Err = String;
+
+	/// Parses a device number in "MAJ:MIN" format, such as "8:0".
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (major, minor) = s
+			.split_once(':')
+			.ok_or_else(|| format!("expected a device number in MAJ:MIN format, such as 8:0; got \"{s}\""))?;
+		let parse = |number: &str| {
+			number
+				.parse()
+				.map_err(|e| format!("invalid device number \"{s}\": {e}"))
+		};
+		Ok(Self {
+			major: parse(major)?,
+			minor: parse(minor)?,
+		})
+	}
+}
+
+impl fmt::Display for DeviceNumber {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(f, "{}:{}", self.major, self.minor)
+	}
+}
+
+/// The throttling limits of a control group for one device, from a line of the `io.max` file.
+///
+/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#io-interface-files>
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::IoLimits;
+/// use cg2tools::Limit;
+///
+/// let limits = IoLimits { rbps: Limit::Value(2097152), ..Default::default() };
+/// assert_eq!(limits.to_string(), "rbps=2097152 wbps=max riops=max wiops=max");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoLimits {
+	/// Maximum bytes read per second.
+	pub rbps: Limit,
+	/// Maximum bytes written per second.
+	pub wbps: Limit,
+	/// Maximum read operations per second.
+	pub riops: Limit,
+	/// Maximum write operations per second.
+	pub wiops: Limit,
+}
+
+impl Default for IoLimits {
+	/// Returns the limits of a device without an entry in `io.max`: no limits.
+	fn default() -> Self {
+		Self {
+			rbps: Limit::Max,
+			wbps: Limit::Max,
+			riops: Limit::Max,
+			wiops: Limit::Max,
+		}
+	}
+}
+
+impl IoLimits {
+	/// Parses the contents of an `io.max` file into limits keyed by device number ("MAJ:MIN").
+	///
+	/// The file only lists devices with limits, so it may be empty. Keys that are missing from a line are unlimited.
+	pub(crate) fn parse(contents: &str) -> Result<HashMap<String, Self>, String> {
+		let mut devices = HashMap::new();
+		for line in contents.lines() {
+			let mut fields = line.split_whitespace();
+			let Some(device) = fields.next() else {
+				continue;
+			};
+			let mut limits = Self::default();
+			for field in fields {
+				let Some((key, value)) = field.split_once('=') else {
+					return Err(format!("invalid field \"{field}\" for device {device}"));
+				};
+				let value = value
+					.parse()
+					.map_err(|e| format!("invalid value for {key} of device {device}: {e}"))?;
+				match key {
+					"rbps" => limits.rbps = value,
+					"wbps" => limits.wbps = value,
+					"riops" => limits.riops = value,
+					"wiops" => limits.wiops = value,
+					_ => return Err(format!("unknown key \"{key}\" for device {device}")),
+				}
+			}
+			devices.insert(device.to_string(), limits);
+		}
+		Ok(devices)
+	}
+}
+
+impl fmt::Display for IoLimits {
+	/// Formats the limits as written to `io.max` after the device number.
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		write!(
+			f,
+			"rbps={} wbps={} riops={} wiops={}",
+			self.rbps, self.wbps, self.riops, self.wiops
+		)
+	}
+}
+
+#[test]
+fn test_parse_io_max() {
+	let devices = IoLimits::parse("8:0 rbps=2097152 wiops=120\n8:16 wbps=max riops=1000\n").unwrap();
+	assert_eq!(devices.len(), 2);
+	assert_eq!(
+		devices["8:0"],
+		IoLimits {
+			rbps: Limit::Value(2097152),
+			wiops: Limit::Value(120),
+			..Default::default()
+		}
+	);
+	assert_eq!(
+		devices["8:16"],
+		IoLimits {
+			riops: Limit::Value(1000),
+			..Default::default()
+		}
+	);
+	assert!(IoLimits::parse("").unwrap().is_empty());
+	assert!(IoLimits::parse("8:0 rbps\n").is_err());
+	assert!(IoLimits::parse("8:0 rbps=fast\n").is_err());
+	assert!(IoLimits::parse("8:0 bogus=1\n").is_err());
+}
+
+#[test]
+fn test_device_number() {
+	assert_eq!("8:0".parse(), Ok(DeviceNumber { major: 8, minor: 0 }));
+	assert!("8".parse::<DeviceNumber>().is_err());
+	assert!("8:x".parse::<DeviceNumber>().is_err());
+	assert_eq!(DeviceNumber::from_dev(0x0801), DeviceNumber { major: 8, minor: 1 });
+	// Large numbers are split across the high and low bits of dev_t
+	let dev = (0x234 << 8) | (0x1000 << 32) | 0x89 | (0x56700 << 12);
+	assert_eq!(
+		DeviceNumber::from_dev(dev),
+		DeviceNumber {
+			major: 0x1234,
+			minor: 0x56789,
+		}
+	);
+	let e = DeviceNumber::from_path("/dev/null").unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+	assert_eq!(
+		DeviceNumber::from_path("/nonexistent").unwrap_err().kind(),
+		io::ErrorKind::NotFound
+	);
+}
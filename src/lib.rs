@@ -23,9 +23,47 @@
 //!
 //! For more information, see [the project README](https://github.com/octave-online/cg2tools?tab=readme-ov-file#cg2tools).
 
+mod audit;
 mod cgroup;
+mod config;
+#[cfg(test)]
+mod conformance;
+mod error;
+mod events;
+mod io;
+mod limit;
+mod memory;
+mod partition;
+mod percentage;
+mod pressure;
+mod profile;
+mod restriction;
+mod snapshot;
+mod stat;
+mod usage;
 
 #[doc(hidden)]
 pub mod internal;
 
 pub use cgroup::CGroup;
+pub use cgroup::CGroupType;
+pub use cgroup::ControllerStatus;
+pub use cgroup::ProvisionReport;
+pub use cgroup::SetOutcome;
+pub use error::CGroupError;
+pub use error::TimeoutError;
+pub use io::DeviceNumber;
+pub use io::IoLimits;
+pub use limit::Limit;
+pub use memory::MemoryEvents;
+pub use memory::MemoryLimits;
+pub use memory::MemoryNumaStat;
+pub use memory::MemoryStat;
+pub use memory::MemoryStats;
+pub use partition::CpusetPartition;
+pub use percentage::Percentage;
+pub use profile::Profile;
+pub use restriction::RestrictionValue;
+pub use snapshot::CGroupSnapshot;
+pub use stat::CGroupStat;
+pub use usage::ControllerUsage;
@@ -0,0 +1,71 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A value that is either a number or unlimited
+
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+/// The value of an interface file that holds either a number or "max", such as `memory.max` or `pids.max`.
+///
+/// [`Limit::Max`] compares greater than every [`Limit::Value`].
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::Limit;
+///
+/// assert_eq!("max".parse(), Ok(Limit::Max));
+/// assert_eq!("1048576\n".parse(), Ok(Limit::Value(1048576)));
+/// assert_eq!(Limit::Value(100).to_string(), "100");
+/// assert!(Limit::Value(u64::MAX) < Limit::Max);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Limit {
+	/// A numeric limit, such as a number of bytes or processes.
+	Value(u64),
+	/// No limit.
+	Max,
+}
+
+impl FromStr for Limit {
+	type Err = ParseIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim() {
+			"max" => Ok(Self::Max),
+			s => s.parse().map(Self::Value),
+		}
+	}
+}
+
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		match self {
+			Self::Value(value) => value.fmt(f),
+			Self::Max => f.write_str("max"),
+		}
+	}
+}
+
+#[test]
+fn test_parse_limit() {
+	assert_eq!("max\n".parse(), Ok(Limit::Max));
+	assert_eq!("0\n".parse(), Ok(Limit::Value(0)));
+	assert_eq!("18446744073709551615".parse(), Ok(Limit::Value(u64::MAX)));
+	assert!("".parse::<Limit>().is_err());
+	assert!("-1".parse::<Limit>().is_err());
+	assert!("max 100000".parse::<Limit>().is_err());
+}
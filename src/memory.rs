@@ -0,0 +1,276 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Types for reading the memory controller's interface files
+
+use crate::Limit;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+/// Event counters from the `memory.events` file of a control group.
+///
+/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#memory-interface-files>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryEvents {
+	/// Number of times the group was reclaimed even though it was below `memory.low`.
+	pub low: u64,
+	/// Number of times the group was throttled and reclaimed for exceeding `memory.high`.
+	pub high: u64,
+	/// Number of times the group's usage was about to go over `memory.max`.
+	pub max: u64,
+	/// Number of times the group's usage reached the limit and allocation failed.
+	pub oom: u64,
+	/// Number of processes in the group killed by the OOM killer.
+	pub oom_kill: u64,
+	/// Number of times the whole group was killed by the OOM killer.
+	pub oom_group_kill: u64,
+}
+
+impl MemoryEvents {
+	/// Builds the counters from a parsed `memory.events` file. Unknown keys are ignored, and missing keys count as
+	/// zero.
+	pub(crate) fn from_events(events: &HashMap<String, u64>) -> Self {
+		let get = |key: &str| events.get(key).copied().unwrap_or(0);
+		Self {
+			low: get("low"),
+			high: get("high"),
+			max: get("max"),
+			oom: get("oom"),
+			oom_kill: get("oom_kill"),
+			oom_group_kill: get("oom_group_kill"),
+		}
+	}
+
+	/// Returns how many times the group hit `memory.high` since an earlier reading.
+	pub fn high_since(&self, earlier: &Self) -> u64 {
+		self.high.saturating_sub(earlier.high)
+	}
+
+	/// Returns true if the group was throttled by `memory.high` since an earlier reading.
+	///
+	/// A group that is constantly being throttled is running at its soft limit and is being reclaimed, which
+	/// usually means `memory.high` is set too low for the workload. Unlike an OOM event, this does not kill any
+	/// processes, so it is an early warning.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::MemoryEvents;
+	///
+	/// let earlier = MemoryEvents { high: 10, ..Default::default() };
+	/// let later = MemoryEvents { high: 12, ..Default::default() };
+	/// assert!(later.throttled_since(&earlier));
+	/// assert!(!earlier.throttled_since(&earlier));
+	/// ```
+	pub fn throttled_since(&self, earlier: &Self) -> bool {
+		self.high_since(earlier) > 0
+	}
+}
+
+/// The breakdown of the memory usage of a control group from its `memory.stat` file, with the most used fields by
+/// name. Memory amounts are in bytes, and event counters are cumulative.
+///
+/// The file has many more fields, which vary between kernel versions; they are kept in [`MemoryStat::other`]. The
+/// whole file is also available with [`CGroup::read_events`](crate::CGroup::read_events).
+///
+/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#memory-interface-files>
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryStat {
+	/// Anonymous memory, such as the heap and stack of processes (`anon`).
+	pub anon: u64,
+	/// Memory used to cache file system data (`file`).
+	pub file: u64,
+	/// Memory allocated to kernel stacks (`kernel_stack`).
+	pub kernel_stack: u64,
+	/// Memory used for in-kernel data structures (`slab`).
+	pub slab: u64,
+	/// Memory used in network transmission buffers (`sock`).
+	pub sock: u64,
+	/// Cached file system data that is swap-backed, such as tmpfs and shared memory (`shmem`).
+	pub shmem: u64,
+	/// Number of page faults (`pgfault`).
+	pub pgfault: u64,
+	/// Number of major page faults, which had to read from disk (`pgmajfault`).
+	pub pgmajfault: u64,
+	/// All other fields of `memory.stat`, by name.
+	pub other: BTreeMap<String, u64>,
+}
+
+impl MemoryStat {
+	/// Builds the breakdown from a parsed `memory.stat` file. Missing fields count as zero.
+	pub(crate) fn from_stat(mut stat: HashMap<String, u64>) -> Self {
+		let mut take = |key: &str| stat.remove(key).unwrap_or(0);
+		Self {
+			anon: take("anon"),
+			file: take("file"),
+			kernel_stack: take("kernel_stack"),
+			slab: take("slab"),
+			sock: take("sock"),
+			shmem: take("shmem"),
+			pgfault: take("pgfault"),
+			pgmajfault: take("pgmajfault"),
+			other: stat.into_iter().collect(),
+		}
+	}
+}
+
+/// The memory limits and protections of a control group.
+///
+/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#memory-interface-files>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLimits {
+	/// Memory that is never reclaimed from the group (`memory.min`).
+	pub min: Limit,
+	/// Memory that is only reclaimed from the group if no unprotected memory is left (`memory.low`).
+	pub low: Limit,
+	/// Usage above which the group is throttled and reclaimed (`memory.high`).
+	pub high: Limit,
+	/// Usage above which the OOM killer is invoked in the group (`memory.max`).
+	pub max: Limit,
+}
+
+impl Default for MemoryLimits {
+	/// Returns the limits of a newly created control group: no protection and no limit.
+	fn default() -> Self {
+		Self {
+			min: Limit::Value(0),
+			low: Limit::Value(0),
+			high: Limit::Max,
+			max: Limit::Max,
+		}
+	}
+}
+
+/// A summary of the memory usage of a control group. See [`MemoryLimits`] for the configured limits.
+///
+/// Optional fields are `None` when the kernel does not provide the corresponding interface file, which depends on
+/// the kernel version and on whether swap and zswap are configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryStats {
+	/// Bytes currently used by the group and its descendants (`memory.current`).
+	pub current: u64,
+	/// Highest usage in bytes recorded for the group (`memory.peak`).
+	pub peak: Option<u64>,
+	/// Bytes of swap currently used (`memory.swap.current`).
+	pub swap_current: Option<u64>,
+	/// Highest swap usage in bytes recorded for the group (`memory.swap.peak`).
+	pub swap_peak: Option<u64>,
+	/// Bytes of compressed memory currently held in zswap (`memory.zswap.current`).
+	pub zswap_current: Option<u64>,
+	/// Limit on the size of the group's zswap pool (`memory.zswap.max`).
+	pub zswap_max: Option<Limit>,
+}
+
+/// The memory usage of a control group broken down by NUMA node, from `memory.numa_stat`.
+///
+/// Maps each node number to the categories of memory on that node, such as "anon" and "file", in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct MemoryNumaStat {
+	/// Bytes per category, keyed by node number.
+	pub nodes: BTreeMap<u32, BTreeMap<String, u64>>,
+}
+
+impl MemoryNumaStat {
+	/// Parses the contents of a `memory.numa_stat` file, which has one line per category such as
+	/// `anon N0=1048576 N1=0`.
+	pub(crate) fn parse(contents: &str) -> Result<Self, String> {
+		let mut stat = Self::default();
+		for line in contents.lines() {
+			let mut fields = line.split_whitespace();
+			let Some(category) = fields.next() else {
+				continue;
+			};
+			for field in fields {
+				let parsed = field
+					.strip_prefix('N')
+					.and_then(|field| field.split_once('='))
+					.and_then(|(node, bytes)| Some((node.parse().ok()?, bytes.parse().ok()?)));
+				let Some((node, bytes)) = parsed else {
+					return Err(format!("invalid field \"{field}\" for {category}"));
+				};
+				stat.nodes.entry(node).or_default().insert(category.to_string(), bytes);
+			}
+		}
+		Ok(stat)
+	}
+}
+
+#[test]
+fn test_parse_memory_numa_stat() {
+	let stat =
+		MemoryNumaStat::parse("anon N0=1048576 N1=4096\nfile N0=0 N1=8192\nkernel_stack N0=16384 N1=0\n").unwrap();
+	assert_eq!(stat.nodes.len(), 2);
+	assert_eq!(stat.nodes[&0]["anon"], 1048576);
+	assert_eq!(stat.nodes[&0]["kernel_stack"], 16384);
+	assert_eq!(stat.nodes[&1]["file"], 8192);
+	assert_eq!(stat.nodes[&1].len(), 3);
+	assert!(MemoryNumaStat::parse("anon N0=x\n").is_err());
+	assert!(MemoryNumaStat::parse("anon 0=5\n").is_err());
+	assert_eq!(MemoryNumaStat::parse(""), Ok(MemoryNumaStat::default()));
+}
+
+#[test]
+fn test_parse_memory_events() {
+	let events = MemoryEvents::from_events(&crate::events::parse(
+		"low 1\nhigh 25\nmax 3\noom 0\noom_kill 0\noom_group_kill 0\n",
+	));
+	assert_eq!(
+		events,
+		MemoryEvents {
+			low: 1,
+			high: 25,
+			max: 3,
+			oom: 0,
+			oom_kill: 0,
+			oom_group_kill: 0,
+		}
+	);
+
+	// Older kernels lack oom_group_kill; newer kernels may add keys we don't know about
+	let events = MemoryEvents::from_events(&crate::events::parse(
+		"low 0\nhigh 7\nmax 0\noom 0\noom_kill 2\nsomething_new 9\n",
+	));
+	assert_eq!(events.high, 7);
+	assert_eq!(events.oom_kill, 2);
+	assert_eq!(events.oom_group_kill, 0);
+}
+
+#[test]
+fn test_memory_stat() {
+	let stat = MemoryStat::from_stat(crate::events::parse(
+		"anon 1048576\nfile 2097152\nkernel_stack 16384\nslab 65536\nsock 0\nshmem 4096\nfile_mapped 8192\npgfault 120\npgmajfault 3\n",
+	));
+	assert_eq!(
+		stat,
+		MemoryStat {
+			anon: 1048576,
+			file: 2097152,
+			kernel_stack: 16384,
+			slab: 65536,
+			sock: 0,
+			shmem: 4096,
+			pgfault: 120,
+			pgmajfault: 3,
+			other: BTreeMap::from([("file_mapped".to_string(), 8192)]),
+		}
+	);
+
+	// Fields may be missing on older kernels
+	let stat = MemoryStat::from_stat(crate::events::parse("anon 5\n"));
+	assert_eq!(stat.anon, 5);
+	assert_eq!(stat.slab, 0);
+	assert!(stat.other.is_empty());
+}
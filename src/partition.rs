@@ -0,0 +1,120 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The partition state of a cpuset control group
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The value of `cpuset.cpus.partition`, which makes a control group the root of a CPU partition.
+///
+/// The kernel reports a partition that cannot currently be honored, for example because its CPUs overlap with a
+/// sibling partition, as "root invalid" or "isolated invalid" followed by a reason in parentheses. Only
+/// [`CpusetPartition::Member`], [`CpusetPartition::Root`], and [`CpusetPartition::Isolated`] can be written.
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::CpusetPartition;
+///
+/// assert_eq!("isolated\n".parse(), Ok(CpusetPartition::Isolated));
+/// let invalid: CpusetPartition = "root invalid (Cpu list in cpuset.cpus not exclusive)".parse().unwrap();
+/// assert!(invalid.is_invalid());
+/// assert_eq!(CpusetPartition::Root.to_string(), "root");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CpusetPartition {
+	/// A normal member of its parent's partition.
+	Member,
+	/// The root of a partition whose CPUs are scheduled normally.
+	Root,
+	/// The root of a partition whose CPUs are excluded from load balancing.
+	Isolated,
+	/// A partition root that the kernel cannot honor, with the reason it reports (empty on older kernels).
+	RootInvalid(String),
+	/// An isolated partition root that the kernel cannot honor, with the reason it reports (empty on older kernels).
+	IsolatedInvalid(String),
+}
+
+impl CpusetPartition {
+	/// Returns whether the kernel reports this partition as invalid.
+	pub fn is_invalid(&self) -> bool {
+		matches!(self, Self::RootInvalid(_) | Self::IsolatedInvalid(_))
+	}
+}
+
+impl FromStr for CpusetPartition {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		let (state, reason) = match s.split_once(" invalid") {
+			Some((state, rest)) => {
+				let reason = rest.trim().trim_start_matches('(').trim_end_matches(')');
+				(state, Some(reason.to_string()))
+			}
+			None => (s, None),
+		};
+		match (state, reason) {
+			("member", None) => Ok(Self::Member),
+			("root", None) => Ok(Self::Root),
+			("isolated", None) => Ok(Self::Isolated),
+			("root", Some(reason)) => Ok(Self::RootInvalid(reason)),
+			("isolated", Some(reason)) => Ok(Self::IsolatedInvalid(reason)),
+			_ => Err(format!("unknown partition state \"{s}\"")),
+		}
+	}
+}
+
+impl fmt::Display for CpusetPartition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		let (state, reason) = match self {
+			Self::Member => return f.write_str("member"),
+			Self::Root => return f.write_str("root"),
+			Self::Isolated => return f.write_str("isolated"),
+			Self::RootInvalid(reason) => ("root", reason),
+			Self::IsolatedInvalid(reason) => ("isolated", reason),
+		};
+		if reason.is_empty() {
+			write!(f, "{state} invalid")
+		} else {
+			write!(f, "{state} invalid ({reason})")
+		}
+	}
+}
+
+#[test]
+fn test_parse_cpuset_partition() {
+	assert_eq!("member\n".parse(), Ok(CpusetPartition::Member));
+	assert_eq!("root".parse(), Ok(CpusetPartition::Root));
+	assert_eq!("root invalid".parse(), Ok(CpusetPartition::RootInvalid(String::new())));
+	assert_eq!(
+		"isolated invalid (Parent is not a partition root)\n".parse(),
+		Ok(CpusetPartition::IsolatedInvalid(
+			"Parent is not a partition root".to_string()
+		))
+	);
+	assert!("".parse::<CpusetPartition>().is_err());
+	assert!("member invalid".parse::<CpusetPartition>().is_err());
+	assert!("exclusive".parse::<CpusetPartition>().is_err());
+	for state in [
+		"member",
+		"root",
+		"isolated",
+		"root invalid",
+		"isolated invalid (No cpu available)",
+	] {
+		assert_eq!(state.parse::<CpusetPartition>().unwrap().to_string(), state);
+	}
+}
@@ -0,0 +1,92 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A percentage with two decimal places, as used by utilization clamping
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A percentage from 0 to 100 with two decimal places, the precision of `cpu.uclamp.min` and `cpu.uclamp.max`.
+///
+/// The kernel writes 100% as "max", and accepts either form.
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::Percentage;
+///
+/// assert_eq!("12.5".parse(), Ok(Percentage::new(12.5).unwrap()));
+/// assert_eq!("max\n".parse(), Ok(Percentage::MAX));
+/// assert_eq!(Percentage::new(12.5).unwrap().to_string(), "12.50");
+/// assert!(Percentage::new(100.5).is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Percentage {
+	hundredths: u16,
+}
+
+impl Percentage {
+	/// 100%, written as "max".
+	pub const MAX: Self = Self { hundredths: 10000 };
+
+	/// Creates a percentage, rounded to two decimal places. Returns an error if it is not between 0 and 100.
+	pub fn new(percent: f64) -> Result<Self, String> {
+		if !(0.0..=100.0).contains(&percent) {
+			return Err(format!("{percent} is not a percentage between 0 and 100"));
+		}
+		Ok(Self {
+			hundredths: (percent * 100.0).round() as u16,
+		})
+	}
+
+	/// Returns the percentage as a number from 0 to 100.
+	pub fn as_f64(&self) -> f64 {
+		f64::from(self.hundredths) / 100.0
+	}
+}
+
+impl FromStr for Percentage {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.trim() {
+			"max" => Ok(Self::MAX),
+			s => Self::new(s.parse().map_err(|_| format!("invalid percentage \"{s}\""))?),
+		}
+	}
+}
+
+impl fmt::Display for Percentage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+		if *self == Self::MAX {
+			f.write_str("max")
+		} else {
+			write!(f, "{}.{:02}", self.hundredths / 100, self.hundredths % 100)
+		}
+	}
+}
+
+#[test]
+fn test_parse_percentage() {
+	for s in ["0.00", "0.01", "12.34", "99.99", "max"] {
+		assert_eq!(s.parse::<Percentage>().unwrap().to_string(), s);
+	}
+	assert_eq!("100".parse(), Ok(Percentage::MAX));
+	assert_eq!("20\n".parse::<Percentage>().unwrap().to_string(), "20.00");
+	assert_eq!("33.333".parse::<Percentage>().unwrap().to_string(), "33.33");
+	assert!("-1".parse::<Percentage>().is_err());
+	assert!("100.01".parse::<Percentage>().is_err());
+	assert!("NaN".parse::<Percentage>().is_err());
+	assert!("half".parse::<Percentage>().is_err());
+}
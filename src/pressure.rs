@@ -0,0 +1,55 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Parsers for pressure stall information (PSI) files, such as `cpu.pressure`
+
+/// Parses the cumulative stall times in microseconds from the "some" and "full" lines of a pressure file.
+///
+/// Each line has the form `some avg10=0.00 avg60=0.00 avg300=0.00 total=12345`. A missing line (kernels before 5.13
+/// do not report "full" for the CPU) counts as zero.
+pub(crate) fn parse_totals(contents: &str) -> Result<(u64, u64), String> {
+	let mut some = 0;
+	let mut full = 0;
+	for line in contents.lines() {
+		let mut fields = line.split_whitespace();
+		let target = match fields.next() {
+			Some("some") => &mut some,
+			Some("full") => &mut full,
+			_ => continue,
+		};
+		let Some(total) = fields.find_map(|field| field.strip_prefix("total=")) else {
+			return Err(format!("missing total in line \"{line}\""));
+		};
+		*target = total
+			.parse()
+			.map_err(|e| format!("invalid total in line \"{line}\": {e}"))?;
+	}
+	Ok((some, full))
+}
+
+#[test]
+fn test_parse_totals() {
+	let contents =
+		"some avg10=1.50 avg60=0.80 avg300=0.20 total=123456789\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=4567\n";
+	assert_eq!(parse_totals(contents), Ok((123456789, 4567)));
+
+	// Kernels before 5.13 have no "full" line in cpu.pressure
+	assert_eq!(
+		parse_totals("some avg10=0.00 avg60=0.00 avg300=0.00 total=42\n"),
+		Ok((42, 0))
+	);
+
+	assert!(parse_totals("some avg10=0.00 avg60=0.00 avg300=0.00\n").is_err());
+	assert!(parse_totals("some avg10=0.00 avg60=0.00 avg300=0.00 total=x\n").is_err());
+}
@@ -0,0 +1,200 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Named sets of controllers and restrictions
+
+use crate::cgroup;
+use crate::internal;
+use crate::restriction;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// A set of controllers and restrictions that can be applied to a control group in one step.
+///
+/// Profiles are text files with one setting per line, using the same syntax as `cg2util control` and
+/// `cg2util restrict`. Blank lines and lines starting with "#" are ignored.
+///
+/// ```text
+/// # Batch jobs get a small share of the CPU and a memory cap
+/// +cpu
+/// +memory
+/// cpu.weight=20
+/// memory.max=1073741824
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+	/// Controllers to enable in the control group.
+	pub controllers: Vec<String>,
+	/// Restrictions to set, as pairs of file name and value.
+	pub restrictions: Vec<(String, String)>,
+}
+
+impl Profile {
+	/// Parses the contents of a profile file.
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::Profile;
+	///
+	/// let profile = Profile::parse("+cpu\n# comment\ncpu.weight=20\n").unwrap();
+	/// assert_eq!(profile.controllers, ["cpu"]);
+	/// assert_eq!(profile.restrictions, [("cpu.weight".to_string(), "20".to_string())]);
+	/// ```
+	pub fn parse(contents: &str) -> Result<Self, String> {
+		let mut profile = Self::default();
+		for (i, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some(controller) = line.strip_prefix('+') {
+				profile.controllers.push(controller.to_string());
+				continue;
+			}
+			match internal::parse_key_value(line) {
+				Ok(restriction) => profile.restrictions.push(restriction),
+				Err(e) => return Err(format!("line {}: {e}", i + 1)),
+			}
+		}
+		Ok(profile)
+	}
+
+	/// Returns the files that [`Profile::load`] checks, in order, for a profile with the given name.
+	///
+	/// A name containing "/" is a path to the profile file. Other names are looked up as `<name>.profile` in
+	/// `$XDG_CONFIG_HOME/cg2tools/profiles` (or `~/.config/cg2tools/profiles`) and then in
+	/// `/etc/cg2tools/profiles`.
+	pub fn search_paths(name: &str) -> Vec<PathBuf> {
+		if name.contains('/') {
+			return vec![PathBuf::from(name)];
+		}
+		let file_name = format!("{name}.profile");
+		internal::config_dirs()
+			.into_iter()
+			.map(|dir| dir.join("profiles").join(&file_name))
+			.collect()
+	}
+
+	/// Checks the contents of a profile file without applying it, returning every problem found rather than only the
+	/// first: lines that cannot be parsed, controllers that are not in `available_controllers`, and values that do not
+	/// match the syntax of well-known interface files, such as "memory.max=lots".
+	///
+	/// # Examples
+	///
+	/// ```
+	/// use cg2tools::Profile;
+	///
+	/// let available = ["cpu".to_string(), "memory".to_string()];
+	/// assert!(Profile::problems("+cpu\ncpu.weight=20\n", &available).is_empty());
+	/// assert_eq!(
+	///     Profile::problems("+io\ncpu.weight=0\n", &available),
+	///     [
+	///         "line 1: controller \"io\" is not available",
+	///         "line 2: cpu.weight: expected a number from 1 to 10000, got \"0\"",
+	///     ]
+	/// );
+	/// ```
+	pub fn problems(contents: &str, available_controllers: &[String]) -> Vec<String> {
+		let mut problems = Vec::new();
+		let is_available = |controller: &str| available_controllers.iter().any(|c| c == controller);
+		for (i, line) in contents.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some(controller) = line.strip_prefix('+') {
+				if !is_available(controller) {
+					problems.push(format!("line {}: controller \"{controller}\" is not available", i + 1));
+				}
+				continue;
+			}
+			let (key, value) = match internal::parse_key_value(line) {
+				Ok(restriction) => restriction,
+				Err(e) => {
+					problems.push(format!("line {}: {e}", i + 1));
+					continue;
+				}
+			};
+			if let Some(controller) = cgroup::controller_for_key(&key).filter(|c| !is_available(c)) {
+				problems.push(format!(
+					"line {}: {key} needs controller \"{controller}\", which is not available",
+					i + 1
+				));
+			}
+			if let Err(e) = restriction::check_value(&key, &value) {
+				problems.push(format!("line {}: {key}: {e}", i + 1));
+			}
+		}
+		problems
+	}
+
+	/// Reads the file of the profile with the given name, returning its path and contents. See
+	/// [`Profile::search_paths`].
+	pub fn read(name: &str) -> io::Result<(PathBuf, String)> {
+		let paths = Self::search_paths(name);
+		for path in paths.iter() {
+			match fs::read_to_string(path) {
+				Ok(contents) => return Ok((path.clone(), contents)),
+				Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+				Err(e) => return Err(e),
+			};
+		}
+		Err(io::Error::new(
+			io::ErrorKind::NotFound,
+			format!("profile \"{name}\" not found in {paths:?}"),
+		))
+	}
+
+	/// Loads the profile with the given name. See [`Profile::search_paths`].
+	pub fn load(name: &str) -> io::Result<Self> {
+		let (path, contents) = Self::read(name)?;
+		Self::parse(&contents)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", path.display())))
+	}
+}
+
+#[test]
+fn test_parse_profile() {
+	let profile = Profile::parse("\n# Batch jobs\n+cpu\n  +memory\ncpu.weight=20\nio.max=8:0 rbps=1048576\n").unwrap();
+	assert_eq!(profile.controllers, ["cpu", "memory"]);
+	assert_eq!(
+		profile.restrictions,
+		[
+			("cpu.weight".to_string(), "20".to_string()),
+			("io.max".to_string(), "8:0 rbps=1048576".to_string()),
+		]
+	);
+	assert_eq!(
+		Profile::parse("+cpu\ncpu.weight\n"),
+		Err("line 2: expected key=value".to_string())
+	);
+	assert_eq!(
+		Profile::parse("weight=20\n"),
+		Err("line 1: key must be of the form CONTROLLER.RESTRICTION".to_string())
+	);
+}
+
+#[test]
+fn test_profile_search_paths() {
+	assert_eq!(
+		Profile::search_paths("./batch.profile"),
+		[PathBuf::from("./batch.profile")]
+	);
+	assert_eq!(
+		Profile::search_paths("batch").last(),
+		Some(&PathBuf::from("/etc/cg2tools/profiles/batch.profile"))
+	);
+}
@@ -0,0 +1,190 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed values for restrictions
+
+use crate::IoLimits;
+use crate::Limit;
+use crate::Percentage;
+use std::time::Duration;
+
+/// Interface files whose values are durations, written in microseconds.
+const MICROSECOND_KEYS: &[&str] = &["cpu.max", "cpu.max.burst"];
+
+/// A value for [`CGroup::set`](crate::CGroup::set), formatted according to the syntax of the file it is written to.
+///
+/// Strings are written as is, so any file can be set with one. Typed values are checked against the file: a
+/// [`Duration`] is only accepted by files that hold a time, such as `cpu.max`, where it sets the quota.
+///
+/// # Examples
+///
+/// ```
+/// use cg2tools::Limit;
+/// use cg2tools::RestrictionValue;
+/// use std::time::Duration;
+///
+/// assert_eq!(RestrictionValue::from("50000 100000").format_for("cpu.max"), Ok("50000 100000".to_string()));
+/// assert_eq!(RestrictionValue::from(Limit::Max).format_for("memory.max"), Ok("max".to_string()));
+/// assert_eq!(RestrictionValue::from(Duration::from_millis(50)).format_for("cpu.max"), Ok("50000".to_string()));
+/// assert!(RestrictionValue::from(Duration::from_millis(50)).format_for("memory.max").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestrictionValue {
+	/// Text written verbatim.
+	Raw(String),
+	/// A number or "max".
+	Limit(Limit),
+	/// A number of bytes.
+	Bytes(u64),
+	/// A time, written in the unit of the file.
+	Duration(Duration),
+}
+
+impl RestrictionValue {
+	/// Formats this value for the interface file `key`, or returns an error if the file does not take this type.
+	pub fn format_for(&self, key: &str) -> Result<String, String> {
+		match self {
+			Self::Raw(value) => Ok(value.clone()),
+			Self::Limit(limit) => Ok(limit.to_string()),
+			Self::Bytes(bytes) => Ok(bytes.to_string()),
+			Self::Duration(duration) if MICROSECOND_KEYS.contains(&key) => Ok(duration.as_micros().to_string()),
+			Self::Duration(_) => Err(format!("{key} does not take a duration")),
+		}
+	}
+}
+
+/// Checks a value against the syntax of a well-known interface file, without writing it. Values of other files are
+/// accepted as they are, since only the kernel knows what they take.
+pub(crate) fn check_value(key: &str, value: &str) -> Result<(), String> {
+	match key {
+		"memory.min" | "memory.low" | "memory.high" | "memory.max" | "memory.swap.high" | "memory.swap.max"
+		| "memory.zswap.max" => check_bytes(value),
+		"pids.max" | "cgroup.max.descendants" | "cgroup.max.depth" => value
+			.parse::<Limit>()
+			.map(drop)
+			.map_err(|e| format!("expected a number or \"max\": {e}")),
+		"cpu.weight" => check_range(value, 1, 10000),
+		"cpu.weight.nice" => check_range(value, -20, 19),
+		"cpu.max" => match value.split_whitespace().collect::<Vec<_>>()[..] {
+			[quota] | [quota, _] if quota != "max" && quota.parse::<u64>().is_err() => {
+				Err(format!("expected a quota in microseconds or \"max\", got \"{quota}\""))
+			}
+			[_] => Ok(()),
+			[_, period] => period
+				.parse::<u64>()
+				.map(drop)
+				.map_err(|e| format!("expected a period in microseconds: {e}")),
+			_ => Err("expected \"$MAX $PERIOD\", such as \"50000 100000\"".to_string()),
+		},
+		"cpu.uclamp.min" | "cpu.uclamp.max" => value.parse::<Percentage>().map(drop),
+		"io.max" => IoLimits::parse(value).map(drop),
+		_ => Ok(()),
+	}
+}
+
+/// Checks a memory size as the kernel parses it: "max", or a number with an optional suffix such as "K" or "G".
+fn check_bytes(value: &str) -> Result<(), String> {
+	let digits = value.trim_end_matches(['k', 'K', 'm', 'M', 'g', 'G', 't', 'T', 'p', 'P', 'e', 'E']);
+	if value == "max" || (value.len() - digits.len() <= 1 && digits.parse::<u64>().is_ok()) {
+		Ok(())
+	} else {
+		Err(format!(
+			"expected a number of bytes, such as \"1G\", or \"max\"; got \"{value}\""
+		))
+	}
+}
+
+/// Checks that a value is an integer in an inclusive range.
+fn check_range(value: &str, min: i64, max: i64) -> Result<(), String> {
+	match value.parse::<i64>() {
+		Ok(n) if (min..=max).contains(&n) => Ok(()),
+		_ => Err(format!("expected a number from {min} to {max}, got \"{value}\"")),
+	}
+}
+
+impl From<&str> for RestrictionValue {
+	fn from(value: &str) -> Self {
+		Self::Raw(value.to_string())
+	}
+}
+
+impl From<String> for RestrictionValue {
+	fn from(value: String) -> Self {
+		Self::Raw(value)
+	}
+}
+
+impl From<Limit> for RestrictionValue {
+	fn from(value: Limit) -> Self {
+		Self::Limit(value)
+	}
+}
+
+impl From<u64> for RestrictionValue {
+	fn from(value: u64) -> Self {
+		Self::Bytes(value)
+	}
+}
+
+impl From<Duration> for RestrictionValue {
+	fn from(value: Duration) -> Self {
+		Self::Duration(value)
+	}
+}
+
+#[test]
+fn test_format_for() {
+	assert_eq!(
+		RestrictionValue::from("8:16 rbps=max").format_for("io.max"),
+		Ok("8:16 rbps=max".to_string())
+	);
+	assert_eq!(
+		RestrictionValue::from(1048576).format_for("memory.high"),
+		Ok("1048576".to_string())
+	);
+	assert_eq!(
+		RestrictionValue::from(Limit::Value(100)).format_for("pids.max"),
+		Ok("100".to_string())
+	);
+	assert_eq!(
+		RestrictionValue::from(Duration::from_micros(2500)).format_for("cpu.max.burst"),
+		Ok("2500".to_string())
+	);
+	assert!(RestrictionValue::from(Duration::from_secs(1))
+		.format_for("cpu.weight")
+		.is_err());
+}
+
+#[test]
+fn test_check_value() {
+	assert_eq!(check_value("memory.max", "1G"), Ok(()));
+	assert_eq!(check_value("memory.high", "max"), Ok(()));
+	assert_eq!(check_value("memory.low", "1073741824"), Ok(()));
+	assert!(check_value("memory.max", "1GB").is_err());
+	assert!(check_value("memory.max", "lots").is_err());
+	assert_eq!(check_value("pids.max", "100"), Ok(()));
+	assert!(check_value("pids.max", "-1").is_err());
+	assert_eq!(check_value("cpu.weight", "10000"), Ok(()));
+	assert!(check_value("cpu.weight", "0").is_err());
+	assert_eq!(check_value("cpu.weight.nice", "-20"), Ok(()));
+	assert_eq!(check_value("cpu.max", "max 100000"), Ok(()));
+	assert_eq!(check_value("cpu.max", "50000"), Ok(()));
+	assert!(check_value("cpu.max", "half 100000").is_err());
+	assert!(check_value("cpu.max", "50000 often").is_err());
+	assert_eq!(check_value("cpu.uclamp.min", "12.5"), Ok(()));
+	assert!(check_value("cpu.uclamp.max", "150").is_err());
+	assert_eq!(check_value("io.max", "8:0 rbps=1048576"), Ok(()));
+	assert!(check_value("io.max", "8:0 speed=fast").is_err());
+	assert_eq!(check_value("cpuset.cpus", "anything"), Ok(()));
+}
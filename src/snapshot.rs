@@ -0,0 +1,36 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The state of a control group captured at one point in time
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The contents of the interface files of a control group, as returned by [`crate::CGroup::snapshot`].
+///
+/// Files of controllers that are not enabled for the group do not exist, so they are simply absent. Values are the
+/// file contents with surrounding whitespace removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CGroupSnapshot {
+	/// Normalized name of the control group.
+	pub cgroup: String,
+	/// Controllers available in the control group, from `cgroup.controllers`.
+	pub controllers: Vec<String>,
+	/// Controllers enabled for children of the control group, from `cgroup.subtree_control`.
+	pub subtree_control: Vec<String>,
+	/// Read-only files, such as "memory.current" and "cpu.stat", by name.
+	pub stats: BTreeMap<String, String>,
+	/// Writable files that hold settings, such as "memory.max" and "cpu.weight", by name.
+	pub settings: BTreeMap<String, String>,
+}
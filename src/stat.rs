@@ -0,0 +1,58 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The counts of descendant control groups from `cgroup.stat`
+
+use std::collections::HashMap;
+
+/// The counts of descendant control groups from the `cgroup.stat` file of a control group.
+///
+/// See <https://docs.kernel.org/admin-guide/cgroup-v2.html#core-interface-files>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CGroupStat {
+	/// Number of visible descendant control groups.
+	pub nr_descendants: u64,
+	/// Number of descendant control groups that were removed but are still being torn down by the kernel. They count
+	/// against `cgroup.max.descendants` until they are gone, and a number that keeps growing usually means that
+	/// something, such as page cache charged to them, keeps them alive.
+	pub nr_dying_descendants: u64,
+}
+
+impl CGroupStat {
+	/// Builds the counts from a parsed `cgroup.stat` file. Unknown keys, such as the per-controller counts of newer
+	/// kernels, are ignored.
+	pub(crate) fn from_stat(stat: &HashMap<String, u64>) -> Result<Self, String> {
+		let get = |key: &str| stat.get(key).copied().ok_or_else(|| format!("missing {key}"));
+		Ok(Self {
+			nr_descendants: get("nr_descendants")?,
+			nr_dying_descendants: get("nr_dying_descendants")?,
+		})
+	}
+}
+
+#[test]
+fn test_from_stat() {
+	let stat = CGroupStat::from_stat(&crate::events::parse("nr_descendants 3\nnr_dying_descendants 1\n")).unwrap();
+	assert_eq!(
+		stat,
+		CGroupStat {
+			nr_descendants: 3,
+			nr_dying_descendants: 1,
+		}
+	);
+	assert_eq!(
+		CGroupStat::from_stat(&crate::events::parse("nr_descendants 3\n")),
+		Err("missing nr_dying_descendants".to_string())
+	);
+}
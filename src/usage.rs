@@ -0,0 +1,103 @@
+// Copyright 2026 Octave Online LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reader for `/proc/cgroups`, the system-wide summary of controller usage
+
+use crate::internal;
+use std::fs;
+use std::io;
+
+/// A line of `/proc/cgroups`, describing how a controller is used across the system.
+///
+/// Unlike `cgroup.controllers`, which is specific to a control group, this covers every control group of both
+/// versions of cgroups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerUsage {
+	/// Name of the controller, such as "memory".
+	pub name: String,
+	/// ID of the cgroups v1 hierarchy that the controller is attached to, or 0 if it is attached to the unified
+	/// hierarchy (cgroups v2) or to none.
+	pub hierarchy: u32,
+	/// Number of control groups that use the controller.
+	pub num_cgroups: u64,
+	/// Whether the controller is enabled. Controllers can be disabled with the `cgroup_disable` kernel parameter.
+	pub enabled: bool,
+}
+
+impl ControllerUsage {
+	/// Reads `/proc/cgroups`, or `cgroups` under the `CG2_PROC_ROOT` environment variable if set.
+	pub fn read_all() -> io::Result<Vec<Self>> {
+		let contents = fs::read_to_string(internal::proc_root().join("cgroups"))?;
+		parse(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+}
+
+/// Parses the tab-separated columns of `/proc/cgroups`, skipping the header line that starts with "#".
+pub(crate) fn parse(contents: &str) -> Result<Vec<ControllerUsage>, String> {
+	let mut controllers = Vec::new();
+	for line in contents.lines() {
+		if line.starts_with('#') || line.trim().is_empty() {
+			continue;
+		}
+		let fields = line.split('\t').collect::<Vec<_>>();
+		let [name, hierarchy, num_cgroups, enabled] = fields[..] else {
+			return Err(format!("expected 4 columns in line \"{line}\""));
+		};
+		let number = |field: &str| {
+			field
+				.trim()
+				.parse::<u64>()
+				.map_err(|e| format!("invalid number in line \"{line}\": {e}"))
+		};
+		controllers.push(ControllerUsage {
+			name: name.to_string(),
+			hierarchy: u32::try_from(number(hierarchy)?)
+				.map_err(|e| format!("invalid hierarchy in line \"{line}\": {e}"))?,
+			num_cgroups: number(num_cgroups)?,
+			enabled: number(enabled)? != 0,
+		});
+	}
+	Ok(controllers)
+}
+
+#[test]
+fn test_parse() {
+	let contents =
+		"#subsys_name\thierarchy\tnum_cgroups\tenabled\ncpuset\t0\t120\t1\nmemory\t4\t40\t1\nhugetlb\t0\t1\t0\n";
+	assert_eq!(
+		parse(contents),
+		Ok(vec![
+			ControllerUsage {
+				name: "cpuset".to_string(),
+				hierarchy: 0,
+				num_cgroups: 120,
+				enabled: true,
+			},
+			ControllerUsage {
+				name: "memory".to_string(),
+				hierarchy: 4,
+				num_cgroups: 40,
+				enabled: true,
+			},
+			ControllerUsage {
+				name: "hugetlb".to_string(),
+				hierarchy: 0,
+				num_cgroups: 1,
+				enabled: false,
+			},
+		])
+	);
+	assert!(parse("cpu\t0\t1\n").is_err());
+	assert!(parse("cpu\t0\tmany\t1\n").is_err());
+}